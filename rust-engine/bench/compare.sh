@@ -0,0 +1,63 @@
+#!/usr/bin/env bash
+# Native vs wasm speed on the same bench suite, for the "Rust in the browser"
+# article. Needs node plus wasm-pack (or wasm-bindgen-cli matching the crate's
+# wasm-bindgen version) and the wasm32-unknown-unknown target.
+#
+#   bench/compare.sh [--simd] [DEPTH]
+#
+# --simd also builds with the wasm-simd feature and compares the two wasm
+# builds, including the piece counting micro-benchmark.
+set -euo pipefail
+
+cd "$(dirname "$0")/.."
+simd=false
+if [ "${1:-}" = "--simd" ]; then
+    simd=true
+    shift
+fi
+depth="${1:-4}"
+
+# build_wasm OUT_DIR [CARGO_FEATURES]
+build_wasm() {
+    if command -v wasm-pack >/dev/null; then
+        wasm-pack build --release --target nodejs --out-dir "$1" -- ${2:+--features "$2"} >&2
+    else
+        cargo build --release --lib --target wasm32-unknown-unknown ${2:+--features "$2"} >&2
+        wasm-bindgen --target nodejs --out-dir "$1" target/wasm32-unknown-unknown/release/rust_engine.wasm
+    fi
+}
+
+build_wasm target/bench-pkg
+$simd && build_wasm target/bench-pkg-simd wasm-simd
+cargo build --release --bin chess_cli >&2
+
+native=$(target/release/chess_cli bench --depth "$depth")
+wasm=$(node bench/run_wasm.js target/bench-pkg "$depth")
+
+field() { echo "$1" | awk -v key="$2" '{ for (i = 1; i < NF; i++) if ($i == key) print $(i + 1) }'; }
+
+native_nodes=$(field "$native" nodes)
+wasm_nodes=$(field "$wasm" nodes)
+if [ "$native_nodes" != "$wasm_nodes" ]; then
+    echo "warning: node counts differ (native $native_nodes, wasm $wasm_nodes)" >&2
+fi
+
+echo "| Build | Depth | Nodes | ms | Nodes/s |"
+echo "|---|---:|---:|---:|---:|"
+echo "| native | $depth | $native_nodes | $(field "$native" ms) | $(field "$native" nps) |"
+echo "| wasm (node) | $depth | $wasm_nodes | $(field "$wasm" ms) | $(field "$wasm" nps) |"
+if $simd; then
+    simd_run=$(node bench/run_wasm.js target/bench-pkg-simd "$depth")
+    echo "| wasm simd128 (node) | $depth | $(field "$simd_run" nodes) | $(field "$simd_run" ms) | $(field "$simd_run" nps) |"
+fi
+awk -v n="$(field "$native" nps)" -v w="$(field "$wasm" nps)" \
+    'BEGIN { printf "\nwasm runs at %.0f%% of native speed\n", 100 * w / n }'
+
+if $simd; then
+    counts=$(node bench/run_wasm.js target/bench-pkg-simd "$depth" counts)
+    echo
+    echo "Piece counting in the wasm-simd build: $(field "$counts" ms) ms with simd128," \
+        "$(field "$counts" scalar_ms) ms scalar ($(field "$counts" speedup)x)"
+    awk -v s="$(field "$simd_run" nps)" -v w="$(field "$wasm" nps)" \
+        'BEGIN { printf "Whole search: the simd128 build runs at %.0f%% of the default wasm build\n", 100 * s / w }'
+fi
@@ -0,0 +1,14 @@
+// Runs the bench suite in the wasm build under node and prints the same line
+// as `chess_cli bench`. Usage: node bench/run_wasm.js PKG_DIR DEPTH [counts]
+// With "counts" it prints the piece counting benchmark instead.
+const path = require("path");
+
+const [pkgDir, depth, mode] = process.argv.slice(2);
+const engine = require(path.resolve(pkgDir, "rust_engine.js"));
+if (mode === "counts") {
+    const [vectorized, fast, scalar, speedup] = engine.run_simd_bench(200000);
+    console.log(`vectorized ${vectorized} ms ${fast.toFixed(1)} scalar_ms ${scalar.toFixed(1)} speedup ${speedup.toFixed(2)}`);
+} else {
+    const [nodes, ms, nps] = engine.run_bench(Number(depth));
+    console.log(`depth ${depth} nodes ${nodes} ms ${ms.toFixed(1)} nps ${nps.toFixed(0)}`);
+}
@@ -0,0 +1,173 @@
+// Time-to-depth report: how long each search configuration takes to reach
+// depth 1, 2, ... on a small position suite, and the deepest search that fits
+// in a time budget. Prints a CSV or Markdown table for the blog.
+//
+//   cargo run --release --bin depth_report -- --max-depth 6 --format markdown
+//
+// Options:
+//   --max-depth N     deepest search to try (default 6)
+//   --budget-ms MS    budget for the "depth reached" summary (default 1000)
+//   --cutoff-ms MS    stop deepening a configuration once one depth takes this long (default 20000)
+//   --format F        csv or markdown (default markdown)
+//   --fens FILE       one FEN per line instead of the built-in suite
+
+use rust_engine::chess::engine::negamax;
+use rust_engine::chess::fen::parse_fen;
+use std::time::Instant;
+
+const SUITE: [&str; 4] = [
+    "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+    "r1bq1rk1/pp2bppp/2n1pn2/3p4/2PP4/2N1PN2/PP3PPP/R2QKB1R w KQ - 0 8",
+    "r3k2r/ppp2ppp/2nqbn2/3pp3/3PP3/2NQBN2/PPP2PPP/R3K2R b KQkq - 0 1",
+    "8/5pk1/6p1/8/3R4/6P1/5PK1/r7 w - - 0 1",
+];
+
+// (name, use_pruning, use_move_ordering)
+const CONFIGS: [(&str, bool, bool); 3] = [
+    ("minimax", false, false),
+    ("alpha-beta", true, false),
+    ("alpha-beta + ordering", true, true),
+];
+
+struct Row {
+    config: &'static str,
+    depth: i32,
+    // Totals over the suite
+    ms: f64,
+    nodes: u64,
+}
+
+fn main() {
+    let mut max_depth = 6;
+    let mut budget_ms = 1000.0;
+    let mut cutoff_ms = 20000.0;
+    let mut markdown = true;
+    let mut fens: Vec<String> = SUITE.iter().map(|fen| fen.to_string()).collect();
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let Some(value) = args.next() else { usage() };
+        match arg.as_str() {
+            "--max-depth" => max_depth = value.parse().unwrap_or_else(|_| usage()),
+            "--budget-ms" => budget_ms = value.parse().unwrap_or_else(|_| usage()),
+            "--cutoff-ms" => cutoff_ms = value.parse().unwrap_or_else(|_| usage()),
+            "--format" => {
+                markdown = match value.as_str() {
+                    "markdown" => true,
+                    "csv" => false,
+                    _ => usage(),
+                }
+            }
+            "--fens" => {
+                let text = std::fs::read_to_string(&value).unwrap_or_else(|err| {
+                    eprintln!("Cannot read {}: {}", value, err);
+                    std::process::exit(1);
+                });
+                fens = text
+                    .lines()
+                    .map(str::trim)
+                    .filter(|line| !line.is_empty() && !line.starts_with('#'))
+                    .map(str::to_string)
+                    .collect();
+            }
+            _ => usage(),
+        }
+    }
+
+    let positions: Vec<_> = fens
+        .iter()
+        .map(|fen| {
+            parse_fen(fen).unwrap_or_else(|err| {
+                eprintln!("Bad FEN {}: {}", fen, err);
+                std::process::exit(1);
+            })
+        })
+        .collect();
+
+    let mut rows = Vec::new();
+    for (config, use_pruning, use_move_ordering) in CONFIGS {
+        for depth in 1..=max_depth {
+            let start = Instant::now();
+            let mut nodes = 0;
+            for position in &positions {
+                let mut board = position.board;
+                let mut eval_count = 0;
+                negamax(
+                    &mut board,
+                    position.color,
+                    depth,
+                    -50000,
+                    50000,
+                    position.castling_rights,
+                    use_pruning,
+                    use_move_ordering,
+                    &mut eval_count,
+                );
+                nodes += eval_count as u64;
+            }
+            let ms = start.elapsed().as_secs_f64() * 1000.0;
+            eprintln!("{} depth {}: {:.1} ms", config, depth, ms);
+            rows.push(Row { config, depth, ms, nodes });
+            if ms > cutoff_ms {
+                break;
+            }
+        }
+    }
+
+    let count = positions.len() as f64;
+    if markdown {
+        println!("| Configuration | Depth | Avg ms / position | Avg leaf nodes | Nodes/s |");
+        println!("|---|---:|---:|---:|---:|");
+    } else {
+        println!("config,depth,avg_ms,avg_nodes,nodes_per_second");
+    }
+    for row in &rows {
+        let nps = row.nodes as f64 / (row.ms / 1000.0).max(1e-9);
+        if markdown {
+            println!(
+                "| {} | {} | {:.1} | {:.0} | {:.0} |",
+                row.config,
+                row.depth,
+                row.ms / count,
+                row.nodes as f64 / count,
+                nps
+            );
+        } else {
+            println!(
+                "{},{},{:.3},{:.0},{:.0}",
+                row.config,
+                row.depth,
+                row.ms / count,
+                row.nodes as f64 / count,
+                nps
+            );
+        }
+    }
+
+    // Deepest depth whose average search time fits in the budget
+    println!();
+    if markdown {
+        println!("| Configuration | Depth within {} ms |", budget_ms);
+        println!("|---|---:|");
+    } else {
+        println!("config,depth_within_{}ms", budget_ms);
+    }
+    for (config, _, _) in CONFIGS {
+        let reached = rows
+            .iter()
+            .filter(|row| row.config == config && row.ms / count <= budget_ms)
+            .map(|row| row.depth)
+            .max()
+            .unwrap_or(0);
+        if markdown {
+            println!("| {} | {} |", config, reached);
+        } else {
+            println!("{},{}", config, reached);
+        }
+    }
+}
+
+fn usage() -> ! {
+    eprintln!("Usage: depth_report [--max-depth N] [--budget-ms MS] [--cutoff-ms MS] [--format csv|markdown] [--fens FILE]");
+    std::process::exit(2);
+}
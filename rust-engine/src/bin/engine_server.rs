@@ -0,0 +1,256 @@
+// Native engine server: the same engine the site runs as wasm, behind a small
+// JSON-RPC style protocol over HTTP, for other frontends and experiments.
+//
+//   cargo run --release --features server --bin engine_server -- --port 8080
+//
+// POST a JSON object {"id": .., "method": .., "params": {..}} to any path and
+// get {"id": .., "result": ..} or {"id": .., "error": ".."} back. Methods:
+//
+//   new_game   {fen?}                -> {game, fen}
+//   make_move  {game, move}          -> {san, fen, status}   move in SAN or coordinates
+//   best_move  {game, depth?}        -> {move, san, fen, status}   plays the move too
+//   analysis   {fen, depth?}         -> {move, san, score, win_probability}
+//   state      {game}                -> {fen, moves, status, pgn}
+//
+// status is null while the game goes on, otherwise the termination reason.
+// score is white-positive in centipawns; mates are +-(10000 - plies to mate).
+
+use rust_engine::chess::engine::{
+    get_best_move, get_opponent, make_move, minimax, win_probability, with_game_history,
+};
+use rust_engine::chess::fen::{parse_fen, square_name, to_fen, FenPosition};
+use rust_engine::chess::game::Game;
+use rust_engine::chess::notation::{move_to_san, parse_move_lenient};
+use rust_engine::chess::pieces::Move;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+
+const DEFAULT_DEPTH: i32 = 4;
+const MAX_DEPTH: i32 = 6;
+const MAX_BODY: usize = 64 * 1024;
+
+#[derive(Default)]
+struct Games {
+    next_id: u64,
+    games: HashMap<u64, Game>,
+}
+
+type Shared = Arc<Mutex<Games>>;
+
+fn main() {
+    let mut port = 8080;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match (arg.as_str(), args.next().and_then(|value| value.parse().ok())) {
+            ("--port", Some(value)) => port = value,
+            _ => {
+                eprintln!("Usage: engine_server [--port N]");
+                std::process::exit(2);
+            }
+        }
+    }
+
+    let listener = TcpListener::bind(("127.0.0.1", port)).unwrap_or_else(|err| {
+        eprintln!("Cannot listen on port {}: {}", port, err);
+        std::process::exit(1);
+    });
+    println!("Listening on http://127.0.0.1:{}", port);
+
+    let games = Shared::default();
+    for stream in listener.incoming().flatten() {
+        let games = games.clone();
+        std::thread::spawn(move || {
+            if let Err(err) = serve(stream, &games) {
+                eprintln!("Connection error: {}", err);
+            }
+        });
+    }
+}
+
+// One request per connection: enough for fetch() and curl
+fn serve(mut stream: TcpStream, games: &Shared) -> std::io::Result<()> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+
+    let mut content_length = 0;
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header)? == 0 || header.trim().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.eq_ignore_ascii_case("content-length") {
+                content_length = value.trim().parse().unwrap_or(0);
+            }
+        }
+    }
+
+    let (status, body) = if request_line.starts_with("OPTIONS ") {
+        // CORS preflight
+        ("204 No Content", String::new())
+    } else if !request_line.starts_with("POST ") {
+        ("405 Method Not Allowed", json!({"error": "POST a JSON request"}).to_string())
+    } else if content_length > MAX_BODY {
+        ("413 Payload Too Large", json!({"error": "Request too large"}).to_string())
+    } else {
+        let mut body = vec![0; content_length];
+        reader.read_exact(&mut body)?;
+        ("200 OK", handle(&body, games).to_string())
+    };
+
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\
+         Access-Control-Allow-Origin: *\r\nAccess-Control-Allow-Headers: Content-Type\r\n\
+         Connection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    )
+}
+
+fn handle(body: &[u8], games: &Shared) -> Value {
+    let request: Value = match serde_json::from_slice(body) {
+        Ok(request) => request,
+        Err(err) => return json!({"id": null, "error": format!("Bad JSON: {}", err)}),
+    };
+    let id = request["id"].clone();
+    let params = &request["params"];
+    let result = match request["method"].as_str() {
+        Some("new_game") => new_game(params, games),
+        Some("make_move") => make_move_rpc(params, games),
+        Some("best_move") => best_move(params, games),
+        Some("analysis") => analysis(params),
+        Some("state") => state(params, games),
+        Some(other) => Err(format!("Unknown method '{}'", other)),
+        None => Err("Missing method".to_string()),
+    };
+    match result {
+        Ok(result) => json!({"id": id, "result": result}),
+        Err(err) => json!({"id": id, "error": err}),
+    }
+}
+
+fn new_game(params: &Value, games: &Shared) -> Result<Value, String> {
+    let game = match params["fen"].as_str() {
+        Some(fen) => Game::from_fen(fen)?,
+        None => Game::new(),
+    };
+    let fen = to_fen(game.position());
+
+    let mut games = games.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    games.next_id += 1;
+    let id = games.next_id;
+    games.games.insert(id, game);
+    Ok(json!({"game": id, "fen": fen}))
+}
+
+fn make_move_rpc(params: &Value, games: &Shared) -> Result<Value, String> {
+    let text = params["move"].as_str().ok_or("Missing move")?;
+    with_game(params, games, |game| {
+        let position = game.position().clone();
+        let move_ = parse_move_lenient(&position.board, position.color, position.castling_rights, text)
+            .ok_or_else(|| format!("Cannot play '{}'", text))?;
+        play(game, &position, move_)
+    })
+}
+
+// The engine moves in the game; the search runs while the game is locked, so
+// other requests for the same server wait for it
+fn best_move(params: &Value, games: &Shared) -> Result<Value, String> {
+    let depth = depth_param(params)?;
+    with_game(params, games, |game| {
+        let position = game.position().clone();
+        let (from, to, _) = with_game_history(&game.history_hashes(), position.halfmove_clock, || {
+            get_best_move(&position.board, position.color, depth, position.castling_rights, true, true, None)
+        })
+        .ok_or("No legal moves")?;
+        let mut result = play(game, &position, (from, to))?;
+        result["move"] = json!(format!("{}{}", square_name(from), square_name(to)));
+        Ok(result)
+    })
+}
+
+fn analysis(params: &Value) -> Result<Value, String> {
+    let position = parse_fen(params["fen"].as_str().ok_or("Missing fen")?)?;
+    let depth = depth_param(params)?;
+    let (from, to, _) = get_best_move(
+        &position.board,
+        position.color,
+        depth,
+        position.castling_rights,
+        true,
+        true,
+        None,
+    )
+    .ok_or("No legal moves")?;
+
+    let mut board = position.board;
+    let (_, rights) = make_move(&mut board, (from, to), position.castling_rights);
+    let mut eval_count = 0;
+    let score = minimax(
+        &mut board,
+        get_opponent(position.color),
+        depth - 1,
+        -50000,
+        50000,
+        rights,
+        true,
+        true,
+        &mut eval_count,
+    );
+    Ok(json!({
+        "move": format!("{}{}", square_name(from), square_name(to)),
+        "san": move_to_san(&position.board, position.color, position.castling_rights, (from, to)),
+        "score": score,
+        "win_probability": win_probability(score, &board),
+    }))
+}
+
+fn state(params: &Value, games: &Shared) -> Result<Value, String> {
+    with_game(params, games, |game| {
+        let sans: Vec<String> = game.move_list().into_iter().map(|(san, _)| san).collect();
+        Ok(json!({
+            "fen": to_fen(game.position()),
+            "moves": sans,
+            "status": status(game),
+            "pgn": game.to_pgn().to_pgn(),
+        }))
+    })
+}
+
+fn play(game: &mut Game, position: &FenPosition, move_: Move) -> Result<Value, String> {
+    if status(game).is_some() {
+        return Err("The game is over".to_string());
+    }
+    let san = move_to_san(&position.board, position.color, position.castling_rights, move_);
+    game.play(move_)?;
+    Ok(json!({"san": san, "fen": to_fen(game.position()), "status": status(game)}))
+}
+
+fn status(game: &Game) -> Option<&'static str> {
+    game.termination().map(|termination| termination.description())
+}
+
+fn with_game(
+    params: &Value,
+    games: &Shared,
+    f: impl FnOnce(&mut Game) -> Result<Value, String>,
+) -> Result<Value, String> {
+    let id = params["game"].as_u64().ok_or("Missing game")?;
+    let mut games = games.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    let game = games.games.get_mut(&id).ok_or_else(|| format!("No game {}", id))?;
+    f(game)
+}
+
+fn depth_param(params: &Value) -> Result<i32, String> {
+    match params["depth"].as_i64() {
+        None => Ok(DEFAULT_DEPTH),
+        Some(depth) if (1..=MAX_DEPTH as i64).contains(&depth) => Ok(depth as i32),
+        Some(depth) => Err(format!("depth must be 1..={}, got {}", MAX_DEPTH, depth)),
+    }
+}
@@ -0,0 +1,227 @@
+// Lichess bot: accepts challenges and plays them with our engine through the
+// Lichess bot API, so the engine can get an online rating. Needs a token with
+// the bot:play scope for an account that has been upgraded to a bot account.
+//
+//   LICHESS_TOKEN=... cargo run --release --features lichess --bin lichess_bot -- --depth 4
+//
+// Only standard chess from the normal start position is accepted. The engine
+// doesn't know en passant or promotion yet; if the opponent plays one of those
+// the game can't be followed any more and the bot resigns it.
+
+use rust_engine::chess::engine::{get_best_move, get_legal_moves};
+use rust_engine::chess::fen::{parse_fen, parse_square, square_name, FenPosition, START_FEN};
+use rust_engine::chess::pieces::{Color, Move};
+use serde_json::Value;
+use std::io::{BufRead, BufReader};
+
+const API: &str = "https://lichess.org/api";
+
+#[derive(Clone)]
+struct Bot {
+    token: String,
+    depth: i32,
+    // Our account id, to tell which side we play
+    id: String,
+}
+
+impl Bot {
+    fn get(&self, path: &str) -> Result<ureq::Response, String> {
+        ureq::get(&format!("{}{}", API, path))
+            .set("Authorization", &format!("Bearer {}", self.token))
+            .call()
+            .map_err(|err| err.to_string())
+    }
+
+    fn post(&self, path: &str) -> Result<ureq::Response, String> {
+        ureq::post(&format!("{}{}", API, path))
+            .set("Authorization", &format!("Bearer {}", self.token))
+            .call()
+            .map_err(|err| err.to_string())
+    }
+
+    // Run f on every JSON line of an NDJSON stream; blank keep-alive lines are skipped
+    fn stream(&self, path: &str, mut f: impl FnMut(Value) -> bool) -> Result<(), String> {
+        let response = self.get(path)?;
+        for line in BufReader::new(response.into_reader()).lines() {
+            let line = line.map_err(|err| err.to_string())?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            match serde_json::from_str(&line) {
+                Ok(event) => {
+                    if !f(event) {
+                        break;
+                    }
+                }
+                Err(err) => eprintln!("Unreadable event {:?}: {}", line, err),
+            }
+        }
+        Ok(())
+    }
+
+    fn handle_challenge(&self, challenge: &Value) {
+        let id = challenge["id"].as_str().unwrap_or_default();
+        let variant = challenge["variant"]["key"].as_str().unwrap_or_default();
+        let result = if variant == "standard" {
+            println!("Accepting challenge {}", id);
+            self.post(&format!("/challenge/{}/accept", id))
+        } else {
+            println!("Declining {} challenge {}", variant, id);
+            ureq::post(&format!("{}/challenge/{}/decline", API, id))
+                .set("Authorization", &format!("Bearer {}", self.token))
+                .send_form(&[("reason", "standard")])
+                .map_err(|err| err.to_string())
+        };
+        if let Err(err) = result {
+            eprintln!("Challenge {}: {}", id, err);
+        }
+    }
+
+    // Follow one game until it ends, moving whenever it is our turn
+    fn play_game(&self, game_id: &str) {
+        let mut our_color = Color::White;
+        let result = self.stream(&format!("/bot/game/stream/{}", game_id), |event| {
+            let state = match event["type"].as_str() {
+                Some("gameFull") => {
+                    if event["black"]["id"].as_str() == Some(self.id.as_str()) {
+                        our_color = Color::Black;
+                    }
+                    if event["initialFen"].as_str().is_some_and(|fen| fen != "startpos" && fen != START_FEN) {
+                        eprintln!("Game {} doesn't start from the normal position", game_id);
+                        self.resign(game_id);
+                        return false;
+                    }
+                    &event["state"]
+                }
+                Some("gameState") => &event,
+                _ => return true,
+            };
+            if state["status"].as_str() != Some("started") {
+                println!("Game {} over: {}", game_id, state["status"].as_str().unwrap_or("?"));
+                return false;
+            }
+
+            let moves = state["moves"].as_str().unwrap_or_default();
+            let Some(position) = replay(moves) else {
+                eprintln!("Game {} left the rules we support after: {}", game_id, moves);
+                self.resign(game_id);
+                return false;
+            };
+            if position.color == our_color {
+                self.move_(game_id, &position);
+            }
+            true
+        });
+        if let Err(err) = result {
+            eprintln!("Game {} stream ended: {}", game_id, err);
+        }
+    }
+
+    fn move_(&self, game_id: &str, position: &FenPosition) {
+        let best_move = get_best_move(
+            &position.board,
+            position.color,
+            self.depth,
+            position.castling_rights,
+            true,
+            true,
+            None,
+        );
+        let Some((from, to, _)) = best_move else {
+            return;
+        };
+        let uci = format!("{}{}", square_name(from), square_name(to));
+        if let Err(err) = self.post(&format!("/bot/game/{}/move/{}", game_id, uci)) {
+            eprintln!("Game {}: move {} rejected: {}", game_id, uci, err);
+        }
+    }
+
+    fn resign(&self, game_id: &str) {
+        if let Err(err) = self.post(&format!("/bot/game/{}/resign", game_id)) {
+            eprintln!("Game {}: resign failed: {}", game_id, err);
+        }
+    }
+}
+
+// Position after a space-separated list of UCI moves, or None if one of them
+// isn't legal under our rules
+fn replay(moves: &str) -> Option<FenPosition> {
+    let mut position = parse_fen(START_FEN).unwrap();
+    for text in moves.split_whitespace() {
+        let move_ = parse_uci_move(text)?;
+        if !get_legal_moves(&position.board, position.color, position.castling_rights).contains(&move_) {
+            return None;
+        }
+        position.play(move_);
+    }
+    Some(position)
+}
+
+fn parse_uci_move(text: &str) -> Option<Move> {
+    if text.len() != 4 || !text.is_ascii() {
+        return None;
+    }
+    Some((parse_square(&text[..2])?, parse_square(&text[2..])?))
+}
+
+fn main() {
+    let Ok(token) = std::env::var("LICHESS_TOKEN") else {
+        eprintln!("Set LICHESS_TOKEN to a bot account's API token");
+        std::process::exit(2);
+    };
+    let mut depth = 4;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match (arg.as_str(), args.next().and_then(|value| value.parse().ok())) {
+            ("--depth", Some(value)) => depth = value,
+            _ => {
+                eprintln!("Usage: lichess_bot [--depth N]");
+                std::process::exit(2);
+            }
+        }
+    }
+
+    let mut bot = Bot {
+        token,
+        depth,
+        id: String::new(),
+    };
+    let account: Result<Value, _> = match bot.get("/account") {
+        Ok(response) => serde_json::from_reader(response.into_reader()),
+        Err(err) => {
+            eprintln!("Cannot reach Lichess: {}", err);
+            std::process::exit(1);
+        }
+    };
+    let account = match account {
+        Ok(account) => account,
+        Err(err) => {
+            eprintln!("Unexpected account response: {}", err);
+            std::process::exit(1);
+        }
+    };
+    bot.id = account["id"].as_str().unwrap_or_default().to_string();
+    println!("Logged in as {}", bot.id);
+
+    let result = bot.stream("/stream/event", |event| {
+        match event["type"].as_str() {
+            Some("challenge") => bot.handle_challenge(&event["challenge"]),
+            Some("gameStart") => {
+                let game_id = event["game"]["gameId"]
+                    .as_str()
+                    .or(event["game"]["id"].as_str())
+                    .unwrap_or_default()
+                    .to_string();
+                println!("Game {} started", game_id);
+                let bot = bot.clone();
+                std::thread::spawn(move || bot.play_game(&game_id));
+            }
+            _ => {}
+        }
+        true
+    });
+    if let Err(err) = result {
+        eprintln!("Event stream ended: {}", err);
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,180 @@
+// Differential tester for move generation: plays random games with shakmaty as
+// the reference implementation and, at every position, compares its legal moves
+// and perft counts with ours. Stops at the first divergence and prints the FEN.
+// Our engine has no en passant and doesn't promote, so those moves are left
+// out on both sides and only counted; everything else still gets compared.
+//
+//   cargo run --release --features oracle --bin movegen_diff -- --games 10000 --seed 7
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use rust_engine::chess::engine::{get_legal_moves, get_opponent, make_move, undo_move, CastlingRights};
+use rust_engine::chess::fen::{parse_fen, square_name};
+use rust_engine::chess::pieces::{Color, Square, WP};
+use shakmaty::fen::Fen;
+use shakmaty::{CastlingMode, Chess, EnPassantMode, Move, Position};
+use std::collections::BTreeSet;
+
+struct Options {
+    games: u32,
+    max_plies: u32,
+    perft_depth: u32,
+    seed: u64,
+}
+
+fn parse_args() -> Options {
+    let mut options = Options {
+        games: 1000,
+        max_plies: 200,
+        perft_depth: 2,
+        seed: rand::rng().random(),
+    };
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let value = args.next().and_then(|value| value.parse::<u64>().ok());
+        let Some(value) = value else {
+            eprintln!("Usage: movegen_diff [--games N] [--max-plies N] [--perft-depth N] [--seed N]");
+            std::process::exit(2);
+        };
+        match arg.as_str() {
+            "--games" => options.games = value as u32,
+            "--max-plies" => options.max_plies = value as u32,
+            "--perft-depth" => options.perft_depth = value as u32,
+            "--seed" => options.seed = value,
+            _ => {
+                eprintln!("Unknown option {}", arg);
+                std::process::exit(2);
+            }
+        }
+    }
+    options
+}
+
+// Moves the reference makes that our engine doesn't model
+fn is_unsupported(m: &Move) -> bool {
+    m.is_en_passant() || m.is_promotion()
+}
+
+// The same moves on our side: a pawn stepping onto the last rank, where it stays a pawn
+fn is_our_promotion(board: &[[i8; 8]; 8], (from, to): (Square, Square)) -> bool {
+    board[from].abs() == WP && (to.rank() == 0 || to.rank() == 7)
+}
+
+fn reference_perft(position: &Chess, depth: u32) -> u64 {
+    let moves = position.legal_moves().into_iter().filter(|m| !is_unsupported(m));
+    if depth == 1 {
+        return moves.count() as u64;
+    }
+    moves
+        .map(|m| {
+            let mut next = position.clone();
+            next.play_unchecked(m);
+            reference_perft(&next, depth - 1)
+        })
+        .sum()
+}
+
+fn our_perft(board: &mut [[i8; 8]; 8], color: Color, castling_rights: CastlingRights, depth: u32) -> u64 {
+    let moves: Vec<_> = get_legal_moves(board, color, castling_rights)
+        .into_iter()
+        .filter(|&move_| !is_our_promotion(board, move_))
+        .collect();
+    if depth == 1 {
+        return moves.len() as u64;
+    }
+    moves
+        .into_iter()
+        .map(|move_| {
+            let (captured, new_rights) = make_move(board, move_, castling_rights);
+            let nodes = our_perft(board, get_opponent(color), new_rights, depth - 1);
+            undo_move(board, move_, captured);
+            nodes
+        })
+        .sum()
+}
+
+// Describe how our engine disagrees with the oracle in this position, if it does
+fn compare(reference: &Chess, perft_depth: u32) -> Option<String> {
+    let fen = Fen::from_position(reference, EnPassantMode::Legal).to_string();
+    let position = match parse_fen(&fen) {
+        Ok(position) => position,
+        Err(err) => return Some(format!("our FEN parser rejected it: {}", err)),
+    };
+
+    let expected: BTreeSet<String> = reference
+        .legal_moves()
+        .iter()
+        .filter(|m| !is_unsupported(m))
+        .map(|m| m.to_uci(CastlingMode::Standard).to_string())
+        .collect();
+    let actual: BTreeSet<String> = get_legal_moves(&position.board, position.color, position.castling_rights)
+        .into_iter()
+        .filter(|&move_| !is_our_promotion(&position.board, move_))
+        .map(|(from, to)| format!("{}{}", square_name(from), square_name(to)))
+        .collect();
+
+    if expected != actual {
+        let missing: Vec<_> = expected.difference(&actual).cloned().collect();
+        let extra: Vec<_> = actual.difference(&expected).cloned().collect();
+        return Some(format!(
+            "legal moves differ\n  missing: {}\n  extra:   {}",
+            missing.join(" "),
+            extra.join(" ")
+        ));
+    }
+
+    for depth in 2..=perft_depth {
+        let expected = reference_perft(reference, depth);
+        let mut board = position.board;
+        let actual = our_perft(&mut board, position.color, position.castling_rights, depth);
+        if expected != actual {
+            return Some(format!(
+                "perft({}) differs: expected {}, got {}",
+                depth, expected, actual
+            ));
+        }
+    }
+    None
+}
+
+fn main() {
+    let options = parse_args();
+    let mut rng = StdRng::seed_from_u64(options.seed);
+    let mut checked: u64 = 0;
+    let mut with_unsupported: u64 = 0;
+
+    println!("Seed {}", options.seed);
+    for game in 0..options.games {
+        let mut reference = Chess::default();
+        for ply in 0..options.max_plies {
+            if reference.is_game_over() {
+                break;
+            }
+
+            if let Some(divergence) = compare(&reference, options.perft_depth) {
+                println!("Divergence in game {}, ply {}", game + 1, ply + 1);
+                println!("FEN: {}", Fen::from_position(&reference, EnPassantMode::Legal));
+                println!("{}", divergence);
+                std::process::exit(1);
+            }
+            checked += 1;
+
+            let moves = reference.legal_moves();
+            if moves.iter().any(is_unsupported) {
+                with_unsupported += 1;
+            }
+            let m = moves[rng.random_range(0..moves.len())];
+            reference.play_unchecked(m);
+        }
+    }
+
+    println!(
+        "No divergence in {} positions over {} games",
+        checked, options.games
+    );
+    println!(
+        "{} of them had en passant or promotion moves, which were left out",
+        with_unsupported
+    );
+}
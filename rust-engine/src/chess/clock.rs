@@ -0,0 +1,224 @@
+// Game clocks for both sides. The caller measures how long each move took
+// and charges it here, so the same code runs the CLI match clocks and the
+// site's clocks in wasm. Times are in milliseconds.
+
+use crate::chess::pieces::Color;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Delay {
+    None,
+    // US (simple) delay: the clock only starts running after the delay
+    Us(u64),
+    // Bronstein: time used is given back afterwards, up to the delay
+    Bronstein(u64),
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct TimeControl {
+    pub initial_ms: u64,
+    pub increment_ms: u64,
+    pub delay: Delay,
+    // Byo-yomi after the main time runs out: `periods` periods of
+    // byo_yomi_ms each. A move made within a period keeps it; using it up
+    // costs one period, and the flag falls after the last.
+    pub byo_yomi_ms: u64,
+    pub periods: u32,
+}
+
+impl TimeControl {
+    pub fn new(initial_ms: u64, increment_ms: u64) -> TimeControl {
+        TimeControl {
+            initial_ms,
+            increment_ms,
+            delay: Delay::None,
+            byo_yomi_ms: 0,
+            periods: 0,
+        }
+    }
+
+    pub fn delay_ms(&self) -> u64 {
+        match self.delay {
+            Delay::None => 0,
+            Delay::Us(ms) | Delay::Bronstein(ms) => ms,
+        }
+    }
+}
+
+// Seconds, for the PGN TimeControl tag and the UI: "300+2", with
+// " delay 3" / " bronstein 3" and " byo-yomi 5x30" when they apply
+impl std::fmt::Display for TimeControl {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let seconds = |ms: u64| ms as f64 / 1000.0;
+        write!(f, "{}+{}", seconds(self.initial_ms), seconds(self.increment_ms))?;
+        match self.delay {
+            Delay::None => {}
+            Delay::Us(ms) => write!(f, " delay {}", seconds(ms))?,
+            Delay::Bronstein(ms) => write!(f, " bronstein {}", seconds(ms))?,
+        }
+        if self.periods > 0 {
+            write!(f, " byo-yomi {}x{}", self.periods, seconds(self.byo_yomi_ms))?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Clock {
+    pub control: TimeControl,
+    // Main time and byo-yomi periods left, white first
+    remaining: [u64; 2],
+    periods: [u32; 2],
+}
+
+fn index(color: Color) -> usize {
+    match color {
+        Color::White => 0,
+        Color::Black => 1,
+    }
+}
+
+impl Clock {
+    pub fn new(control: TimeControl) -> Clock {
+        Clock {
+            control,
+            remaining: [control.initial_ms; 2],
+            periods: [control.periods; 2],
+        }
+    }
+
+    pub fn remaining(&self, color: Color) -> u64 {
+        self.remaining[index(color)]
+    }
+
+    pub fn periods_left(&self, color: Color) -> u32 {
+        self.periods[index(color)]
+    }
+
+    // Main time used up and playing on byo-yomi periods
+    pub fn in_byo_yomi(&self, color: Color) -> bool {
+        self.remaining(color) == 0 && self.periods_left(color) > 0
+    }
+
+    // Charge `elapsed_ms` for the move `color` just made and add whatever the
+    // time control gives back. Returns false if the flag fell.
+    pub fn charge(&mut self, color: Color, elapsed_ms: u64) -> bool {
+        let i = index(color);
+        let charged = match self.control.delay {
+            Delay::Us(delay) => elapsed_ms.saturating_sub(delay),
+            _ => elapsed_ms,
+        };
+
+        if !self.in_byo_yomi(color) && charged <= self.remaining[i] {
+            self.remaining[i] -= charged;
+            self.remaining[i] += self.control.increment_ms;
+            if let Delay::Bronstein(delay) = self.control.delay {
+                self.remaining[i] += elapsed_ms.min(delay);
+            }
+            return true;
+        }
+
+        // Main time is gone; whatever is left over eats into byo-yomi
+        let mut over = charged - self.remaining[i];
+        self.remaining[i] = 0;
+        while self.periods[i] > 0 {
+            if over < self.control.byo_yomi_ms {
+                return true;
+            }
+            over -= self.control.byo_yomi_ms;
+            self.periods[i] -= 1;
+        }
+        false
+    }
+
+    // Longest the side can think on its next move without losing on time:
+    // the US delay, the main time and every period left
+    pub fn time_for_move(&self, color: Color) -> u64 {
+        let delay = match self.control.delay {
+            Delay::Us(delay) => delay,
+            _ => 0,
+        };
+        // A period has to be left before it runs out, main time can be used to the end
+        let byo_yomi = (self.periods_left(color) as u64 * self.control.byo_yomi_ms).saturating_sub(1);
+        delay + self.remaining(color) + byo_yomi
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn control(delay: Delay) -> TimeControl {
+        TimeControl { delay, ..TimeControl::new(10_000, 0) }
+    }
+
+    #[test]
+    fn increment_and_flag_without_byo_yomi() {
+        let mut clock = Clock::new(TimeControl::new(10_000, 1_000));
+        assert!(clock.charge(Color::White, 500));
+        assert_eq!(clock.remaining(Color::White), 10_500);
+        assert_eq!(clock.remaining(Color::Black), 10_000);
+        assert!(clock.charge(Color::White, 10_500));
+        assert_eq!(clock.remaining(Color::White), 1_000);
+        assert!(!clock.charge(Color::White, 1_001));
+    }
+
+    #[test]
+    fn us_delay_is_free_time() {
+        let mut clock = Clock::new(control(Delay::Us(2_000)));
+        assert!(clock.charge(Color::White, 1_500));
+        assert_eq!(clock.remaining(Color::White), 10_000);
+        assert!(clock.charge(Color::White, 3_000));
+        assert_eq!(clock.remaining(Color::White), 9_000);
+        assert_eq!(clock.time_for_move(Color::White), 11_000);
+    }
+
+    #[test]
+    fn bronstein_gives_back_up_to_the_delay() {
+        let mut clock = Clock::new(control(Delay::Bronstein(2_000)));
+        assert!(clock.charge(Color::White, 1_500));
+        assert_eq!(clock.remaining(Color::White), 10_000);
+        assert!(clock.charge(Color::White, 3_000));
+        assert_eq!(clock.remaining(Color::White), 9_000);
+        assert_eq!(clock.time_for_move(Color::White), 9_000);
+    }
+
+    #[test]
+    fn charge_across_byo_yomi_periods() {
+        let mut clock = Clock::new(TimeControl {
+            byo_yomi_ms: 500,
+            periods: 3,
+            ..TimeControl::new(1_000, 0)
+        });
+        assert!(clock.charge(Color::White, 800));
+        assert!(!clock.in_byo_yomi(Color::White));
+        assert_eq!(clock.time_for_move(Color::White), 200 + 1_499);
+
+        // 900 over the main time: one period used up, the move made in the next
+        assert!(clock.charge(Color::White, 1_100));
+        assert!(clock.in_byo_yomi(Color::White));
+        assert_eq!(clock.periods_left(Color::White), 2);
+
+        // A move inside the period keeps it
+        assert!(clock.charge(Color::White, 499));
+        assert_eq!(clock.periods_left(Color::White), 2);
+        assert!(clock.charge(Color::White, 500));
+        assert_eq!(clock.periods_left(Color::White), 1);
+        assert_eq!(clock.time_for_move(Color::White), 499);
+
+        assert!(!clock.charge(Color::White, 500));
+        assert_eq!(clock.periods_left(Color::White), 0);
+        assert_eq!(clock.periods_left(Color::Black), 3);
+    }
+
+    #[test]
+    fn display_in_seconds() {
+        let control = TimeControl {
+            delay: Delay::Bronstein(3_000),
+            byo_yomi_ms: 30_000,
+            periods: 5,
+            ..TimeControl::new(300_000, 2_000)
+        };
+        assert_eq!(control.to_string(), "300+2 bronstein 3 byo-yomi 5x30");
+        assert_eq!(TimeControl::new(90_500, 0).to_string(), "90.5+0");
+    }
+}
@@ -0,0 +1,63 @@
+// Piece-level difference between two boards, for animating a jump between
+// any two points of a game or loading a new FEN. Castling comes out as two
+// moves (king and rook) and en passant as a move plus a removal, without
+// either being special-cased: pieces that left a square are paired with
+// pieces of the same kind that arrived somewhere, closest pairs first.
+
+use crate::chess::pieces::{Square, E};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PieceChange {
+    Moved { piece: i8, from: Square, to: Square },
+    Added { piece: i8, square: Square },
+    Removed { piece: i8, square: Square },
+}
+
+fn distance(a: Square, b: Square) -> usize {
+    a.rank().abs_diff(b.rank()).max(a.file().abs_diff(b.file()))
+}
+
+// Moves first, then removals, then additions, so the UI can start the slides
+// before fading captured pieces out and new ones in
+pub fn diff_positions(before: &[[i8; 8]; 8], after: &[[i8; 8]; 8]) -> Vec<PieceChange> {
+    let mut vacated = Vec::new();
+    let mut arrived = Vec::new();
+    for square in Square::all() {
+        if before[square] == after[square] {
+            continue;
+        }
+        if before[square] != E {
+            vacated.push(Some((before[square], square)));
+        }
+        if after[square] != E {
+            arrived.push(Some((after[square], square)));
+        }
+    }
+
+    let mut pairs = Vec::new();
+    for (i, &(piece, from)) in vacated.iter().flatten().enumerate() {
+        // Nothing is matched yet, so flattening keeps the indices
+        for (j, &(other, to)) in arrived.iter().flatten().enumerate() {
+            if piece == other {
+                pairs.push((distance(from, to), i, j));
+            }
+        }
+    }
+    pairs.sort_unstable();
+
+    let mut changes = Vec::new();
+    for (_, i, j) in pairs {
+        if let (Some((piece, from)), Some((_, to))) = (vacated[i], arrived[j]) {
+            changes.push(PieceChange::Moved { piece, from, to });
+            vacated[i] = None;
+            arrived[j] = None;
+        }
+    }
+    for (piece, square) in vacated.into_iter().flatten() {
+        changes.push(PieceChange::Removed { piece, square });
+    }
+    for (piece, square) in arrived.into_iter().flatten() {
+        changes.push(PieceChange::Added { piece, square });
+    }
+    changes
+}
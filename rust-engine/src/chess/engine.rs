@@ -1,8 +1,17 @@
 use crate::chess::pieces::{
-    get_all_pseudo_legal_moves, get_piece_value, get_pseudo_legal_moves_for_piece, Color, BK, BP, BQ,
-    BR, E, WB, WK, WN, WP, WQ, WR,
+    get_all_pseudo_legal_moves, get_piece_color, get_piece_value, Color, Move, Square, BK, BR, E, WB, WK, WN, WP, WQ, WR,
 };
+use crate::chess::eval::{evaluate, PAWN_VALUE};
+use crate::chess::fen::FenPosition;
+use crate::chess::status::{get_termination, Termination};
+use crate::chess::tables::{zobrist_hash, ALL_DIRECTIONS, DIAGONAL, KING_TARGETS, KNIGHT_TARGETS, ORTHOGONAL, RAYS};
+use crate::chess::timing::now_ms;
 use rand::prelude::IndexedRandom;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::cell::{Cell, RefCell};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 
 pub const CASTLE_WK: u8 = 1;
 pub const CASTLE_WQ: u8 = 2;
@@ -10,6 +19,398 @@ pub const CASTLE_BK: u8 = 4;
 pub const CASTLE_BQ: u8 = 8;
 pub const ALL_CASTLE_RIGHTS: u8 = 15;
 
+// Castling rights: only the four CASTLE_* bits can be set. Checked on the way
+// in where rights come from outside (FEN, the v2 protocol, the wasm exports),
+// then passed through the engine API as is; it is a single byte to copy.
+#[derive(PartialEq, Eq, Copy, Clone, Debug, Default)]
+pub struct CastlingRights(u8);
+
+impl CastlingRights {
+    pub const NONE: CastlingRights = CastlingRights(0);
+    pub const ALL: CastlingRights = CastlingRights(ALL_CASTLE_RIGHTS);
+
+    pub fn from_bits(bits: u8) -> Option<CastlingRights> {
+        (bits & !ALL_CASTLE_RIGHTS == 0).then_some(CastlingRights(bits))
+    }
+
+    // Any bits besides the four CASTLE_* flags dropped, which is how the
+    // wasm exports have always read the rights JS passes in
+    pub fn from_bits_truncate(bits: u8) -> CastlingRights {
+        CastlingRights(bits & ALL_CASTLE_RIGHTS)
+    }
+
+    pub fn bits(self) -> u8 {
+        self.0
+    }
+
+    // `mask` is one or more CASTLE_* flags; true if any of them is set
+    pub fn has(self, mask: u8) -> bool {
+        self.0 & mask != 0
+    }
+
+    pub fn without(self, mask: u8) -> CastlingRights {
+        CastlingRights(self.0 & !mask)
+    }
+
+    // The FEN castling field: "KQkq", any subset of it in that order, or "-"
+    pub fn from_fen(field: &str) -> Result<CastlingRights, String> {
+        if field == "-" {
+            return Ok(CastlingRights::NONE);
+        }
+        let mut bits = 0;
+        for c in field.chars() {
+            bits |= match c {
+                'K' => CASTLE_WK,
+                'Q' => CASTLE_WQ,
+                'k' => CASTLE_BK,
+                'q' => CASTLE_BQ,
+                _ => return Err(format!("Unknown castling flag '{}'", c)),
+            };
+        }
+        Ok(CastlingRights(bits))
+    }
+}
+
+impl std::fmt::Display for CastlingRights {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        if self.0 == 0 {
+            return write!(f, "-");
+        }
+        for (mask, c) in [(CASTLE_WK, 'K'), (CASTLE_WQ, 'Q'), (CASTLE_BK, 'k'), (CASTLE_BQ, 'q')] {
+            if self.has(mask) {
+                write!(f, "{}", c)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+thread_local! {
+    // Tie-breaks in get_best_move and the hint noise. StdRng produces the same
+    // stream natively and in wasm, so after set_seed a game replays exactly
+    // the same in the CLI and in the browser.
+    static RNG: RefCell<StdRng> = RefCell::new(StdRng::from_os_rng());
+}
+
+// Make this thread's engine randomness reproducible from here on
+pub fn set_seed(seed: u64) {
+    RNG.with(|rng| *rng.borrow_mut() = StdRng::seed_from_u64(seed));
+}
+
+// Stop conditions for a limited search, checked from inside the tree. Per
+// thread like RNG, so native searches running side by side don't stop each other.
+#[derive(Clone, Copy, Default)]
+struct SearchLimits {
+    deadline_ms: Option<f64>,
+    max_nodes: Option<u64>,
+    nodes: u64,
+    stopped: bool,
+}
+
+thread_local! {
+    static LIMITS: Cell<SearchLimits> = Cell::new(SearchLimits::default());
+    // Set from another thread (a Ctrl-C or stop button handler) to end the search
+    static STOP_TOKEN: RefCell<Option<Arc<AtomicBool>>> = const { RefCell::new(None) };
+}
+
+// Triangular principal variation table: lines[ply] is the best line found
+// from the node at `ply` of the current search, rebuilt from lines[ply + 1]
+// whenever a move there raises alpha
+const MAX_PLY: usize = 64;
+
+thread_local! {
+    static PV_TABLE: RefCell<Vec<Vec<Move>>> = RefCell::new(vec![Vec::new(); MAX_PLY + 1]);
+}
+
+fn pv_clear(ply: i32) {
+    PV_TABLE.with(|table| {
+        if let Some(line) = table.borrow_mut().get_mut(ply as usize) {
+            line.clear();
+        }
+    });
+}
+
+fn pv_update(ply: i32, move_: Move) {
+    let ply = ply as usize;
+    if ply >= MAX_PLY {
+        return;
+    }
+    PV_TABLE.with(|table| {
+        let mut table = table.borrow_mut();
+        let (head, tail) = table.split_at_mut(ply + 1);
+        head[ply].clear();
+        head[ply].push(move_);
+        head[ply].extend_from_slice(&tail[0]);
+    });
+}
+
+fn pv_line(ply: i32) -> Vec<Move> {
+    PV_TABLE.with(|table| table.borrow()[ply as usize].clone())
+}
+
+// Selective depth: the deepest ply any node of the current root move's search
+// reached, quiescence included
+thread_local! {
+    static SELDEPTH: Cell<i32> = const { Cell::new(0) };
+}
+
+fn note_ply(ply: i32) {
+    SELDEPTH.with(|cell| cell.set(cell.get().max(ply)));
+}
+
+// Beta cutoffs are counted by the index of the move that caused them; later
+// moves share the last bucket
+pub const CUTOFF_BUCKETS: usize = 8;
+
+// What the search did, for judging whether a pruning rule pays for itself.
+// Only collected inside with_stats.
+#[derive(Clone, Copy, Default, Debug)]
+pub struct SearchStats {
+    // Main search and quiescence nodes
+    pub nodes: u64,
+    pub qnodes: u64,
+    pub beta_cutoffs: [u64; CUTOFF_BUCKETS],
+    pub reverse_futility_cutoffs: u64,
+    pub razor_cutoffs: u64,
+    pub futility_skips: u64,
+    pub delta_skips: u64,
+}
+
+impl SearchStats {
+    // Share of beta cutoffs made by the first move, the usual measure of move ordering
+    pub fn first_move_cutoff_rate(&self) -> f64 {
+        let total: u64 = self.beta_cutoffs.iter().sum();
+        self.beta_cutoffs[0] as f64 / total.max(1) as f64
+    }
+}
+
+thread_local! {
+    static STATS: Cell<Option<SearchStats>> = const { Cell::new(None) };
+}
+
+// Run a search and collect its statistics. Root moves are searched on this
+// thread while collecting, so nothing is lost to other threads.
+pub fn with_stats<T>(search: impl FnOnce() -> T) -> (T, SearchStats) {
+    let previous = STATS.with(|cell| cell.replace(Some(SearchStats::default())));
+    let result = search();
+    let stats = STATS.with(|cell| cell.replace(previous)).unwrap_or_default();
+    (result, stats)
+}
+
+fn record(update: impl FnOnce(&mut SearchStats)) {
+    STATS.with(|cell| {
+        if let Some(mut stats) = cell.get() {
+            update(&mut stats);
+            cell.set(Some(stats));
+        }
+    });
+}
+
+#[cfg(any(not(target_arch = "wasm32"), feature = "wasm-threads"))]
+fn stats_active() -> bool {
+    STATS.with(|cell| cell.get().is_some())
+}
+
+// Zobrist hashes of the positions before the current node: the game so far
+// (see with_game_history), then the line the search is down
+thread_local! {
+    static HISTORY: RefCell<Vec<u64>> = const { RefCell::new(Vec::new()) };
+    // Halfmove clock of the position the search starts from
+    static ROOT_HALFMOVE_CLOCK: Cell<u32> = const { Cell::new(0) };
+}
+
+// Run a search with the hashes of the game's earlier positions, oldest first,
+// so lines that repeat one of them score as draws, and the halfmove clock of
+// the position searched, so lines that reach the fifty-move rule do too
+pub fn with_game_history<T>(history: &[u64], halfmove_clock: u32, search: impl FnOnce() -> T) -> T {
+    let previous = HISTORY.with(|cell| cell.replace(history.to_vec()));
+    let previous_clock = ROOT_HALFMOVE_CLOCK.with(|cell| cell.replace(halfmove_clock));
+    let result = search();
+    HISTORY.with(|cell| cell.replace(previous));
+    ROOT_HALFMOVE_CLOCK.with(|cell| cell.set(previous_clock));
+    result
+}
+
+fn root_halfmove_clock() -> u32 {
+    ROOT_HALFMOVE_CLOCK.with(|cell| cell.get())
+}
+
+// Halfmove clock after move_ was made on board: captures and pawn moves reset it
+fn next_halfmove_clock(board: &[[i8; 8]; 8], move_: Move, captured: i8, halfmove_clock: u32) -> u32 {
+    let (_, to) = move_;
+    if captured != E || board[to].abs() == WP {
+        0
+    } else {
+        halfmove_clock + 1
+    }
+}
+
+fn history_push(hash: u64) {
+    HISTORY.with(|cell| cell.borrow_mut().push(hash));
+}
+
+fn history_pop() {
+    HISTORY.with(|cell| cell.borrow_mut().pop());
+}
+
+fn is_repetition(hash: u64) -> bool {
+    HISTORY.with(|cell| cell.borrow().contains(&hash))
+}
+
+// Nodes between clock reads
+const STOP_CHECK_INTERVAL: u64 = 1024;
+
+// Count a node of the search and say whether it has to stop
+fn count_node() -> bool {
+    LIMITS.with(|cell| {
+        let mut limits = cell.get();
+        if !limits.stopped {
+            limits.nodes += 1;
+            limits.stopped = limits.max_nodes.is_some_and(|max| limits.nodes > max)
+                || (limits.nodes.is_multiple_of(STOP_CHECK_INTERVAL)
+                    && (limits.deadline_ms.is_some_and(|deadline| now_ms() >= deadline) || stop_requested()));
+            cell.set(limits);
+        }
+        limits.stopped
+    })
+}
+
+fn stop_requested() -> bool {
+    STOP_TOKEN.with(|token| token.borrow().as_ref().is_some_and(|stop| stop.load(Ordering::Relaxed)))
+}
+
+// A deadline, node cap or stop token is set for this thread's search
+#[cfg(any(not(target_arch = "wasm32"), feature = "wasm-threads"))]
+fn limits_active() -> bool {
+    let limits = LIMITS.with(|limits| limits.get());
+    limits.deadline_ms.is_some()
+        || limits.max_nodes.is_some()
+        || STOP_TOKEN.with(|token| token.borrow().is_some())
+}
+
+fn search_stopped() -> bool {
+    LIMITS.with(|limits| limits.get().stopped)
+}
+
+fn nodes_searched() -> u64 {
+    LIMITS.with(|limits| limits.get().nodes)
+}
+
+fn set_limits(limits: SearchLimits) {
+    LIMITS.with(|cell| cell.set(limits));
+}
+
+// Material lead (in pawns) above which leaf nodes are checked for stalemate
+pub(crate) const STALEMATE_CHECK_MARGIN: i32 = 5;
+
+// Search settings for a website mode, so a mode maps to one string: the depth
+// and the shallow-depth pruning margins, each indexed by remaining depth.
+// Smaller margins prune more, which the faster modes trade for speed.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct SearchPreset {
+    pub name: &'static str,
+    pub depth: i32,
+    // Futility: a quiet move is assumed unable to win back more than this
+    pub futility_margins: [i32; 3],
+    // Reverse futility: how much a side standing above beta could still lose
+    // over that many plies
+    pub reverse_futility_margins: [i32; 4],
+    // Razoring: a position this far below alpha is assumed lost unless a
+    // capture sequence saves it
+    pub razor_margins: [i32; 3],
+}
+
+impl SearchPreset {
+    pub const BULLET: SearchPreset = SearchPreset {
+        name: "bullet",
+        depth: 2,
+        futility_margins: [0, PAWN_VALUE + PAWN_VALUE / 2, 4 * PAWN_VALUE],
+        reverse_futility_margins: [0, PAWN_VALUE, 2 * PAWN_VALUE, 3 * PAWN_VALUE],
+        razor_margins: [0, 2 * PAWN_VALUE + PAWN_VALUE / 2, 4 * PAWN_VALUE],
+    };
+    pub const BLITZ: SearchPreset = SearchPreset {
+        name: "blitz",
+        depth: 3,
+        ..SearchPreset::RAPID
+    };
+    pub const RAPID: SearchPreset = SearchPreset {
+        name: "rapid",
+        depth: 4,
+        futility_margins: [0, 2 * PAWN_VALUE, 5 * PAWN_VALUE],
+        reverse_futility_margins: [0, PAWN_VALUE + PAWN_VALUE / 5, 2 * PAWN_VALUE + PAWN_VALUE / 2, 4 * PAWN_VALUE],
+        razor_margins: [0, 3 * PAWN_VALUE, 5 * PAWN_VALUE + PAWN_VALUE / 2],
+    };
+    pub const ANALYSIS: SearchPreset = SearchPreset {
+        name: "analysis",
+        depth: 5,
+        futility_margins: [0, 3 * PAWN_VALUE, 7 * PAWN_VALUE],
+        reverse_futility_margins: [0, 2 * PAWN_VALUE, 4 * PAWN_VALUE, 6 * PAWN_VALUE],
+        razor_margins: [0, 4 * PAWN_VALUE, 7 * PAWN_VALUE],
+    };
+    pub const ALL: [SearchPreset; 4] = [SearchPreset::BULLET, SearchPreset::BLITZ, SearchPreset::RAPID, SearchPreset::ANALYSIS];
+
+    // A preset by name, in any letter case
+    pub fn from_name(name: &str) -> Option<SearchPreset> {
+        SearchPreset::ALL.into_iter().find(|preset| preset.name.eq_ignore_ascii_case(name))
+    }
+}
+
+// Rapid's margins are the ones the engine was tuned with
+impl Default for SearchPreset {
+    fn default() -> Self {
+        SearchPreset::RAPID
+    }
+}
+
+thread_local! {
+    static PRESET: Cell<SearchPreset> = const { Cell::new(SearchPreset::RAPID) };
+}
+
+// Run a search with the margins of `preset`. The depth is still whatever the
+// search is called with; pass preset.depth for the preset's own.
+pub fn with_preset<T>(preset: SearchPreset, search: impl FnOnce() -> T) -> T {
+    let previous = PRESET.with(|cell| cell.replace(preset));
+    let result = search();
+    PRESET.with(|cell| cell.set(previous));
+    result
+}
+
+fn current_preset() -> SearchPreset {
+    PRESET.with(|cell| cell.get())
+}
+
+// Delta pruning slack in quiescence, for positional gains a capture brings
+const DELTA_MARGIN: i32 = 2 * PAWN_VALUE;
+
+// Being mated `ply` half-moves below the node the search started from scores
+// -(MATE_SCORE - ply), so a shorter mate always outscores a longer one. Root
+// moves are searched at ply 1, so their scores count plies from the root.
+pub const MATE_SCORE: i32 = 10000;
+
+// Internal iterative deepening: minimum remaining depth, and how much
+// shallower the move-picking search is
+const IID_MIN_DEPTH: i32 = 4;
+const IID_REDUCTION: i32 = 2;
+
+// Scores this far from zero are mates, which no margin argument applies to
+pub const MATE_BOUND: i32 = 9000;
+
+pub fn is_mate_score(score: i32) -> bool {
+    score.abs() >= MATE_BOUND
+}
+
+// Full moves to mate for a root move's score, for "M3" displays: positive when
+// the side the score is for mates, negative when it gets mated, None otherwise
+pub fn mate_in(score: i32) -> Option<i32> {
+    if !is_mate_score(score) {
+        None
+    } else if score > 0 {
+        Some((MATE_SCORE - score + 1) / 2)
+    } else {
+        Some(-(MATE_SCORE + score) / 2)
+    }
+}
+
 pub fn evaluate_board(board: &[[i8; 8]; 8]) -> i32 {
     let mut total_point = 0;
     for row in board {
@@ -27,10 +428,14 @@ pub fn get_opponent(color: Color) -> Color {
     }
 }
 
-pub fn score_move(board: &[[i8; 8]; 8], move_: ((usize, usize), (usize, usize))) -> i32 {
-    let ((from_r, from_f), (to_r, to_f)) = move_;
-    let move_piece = board[from_r][from_f];
-    let captured_piece = board[to_r][to_f];
+// Move ordering bonus for a check, in the same units as MVV-LVA (10 per pawn
+// of victim): worth a pawn on top
+const CHECK_BONUS: i32 = 10;
+
+pub(crate) fn score_move(board: &[[i8; 8]; 8], move_: Move) -> i32 {
+    let (from, to) = move_;
+    let move_piece = board[from];
+    let captured_piece = board[to];
 
     if captured_piece != E {
         // MVV-LVA: 10 * Victim Value - Attacker Value
@@ -43,17 +448,139 @@ pub fn score_move(board: &[[i8; 8]; 8], move_: ((usize, usize), (usize, usize)))
     0
 }
 
-pub fn make_move(
-    board: &mut [[i8; 8]; 8],
-    move_: ((usize, usize), (usize, usize)),
-    current_rights: u8,
-) -> (i8, u8) {
-    let ((from_r, from_f), (to_r, to_f)) = move_;
-    let piece = board[from_r][from_f];
-    let captured = board[to_r][to_f];
+// score_move with checks against `their_king` ranked up too. Finding them
+// means playing each move out, so only the root, PV nodes and quiescence pay
+// for it.
+fn score_move_with_check(board: &[[i8; 8]; 8], move_: Move, their_king: Option<Square>) -> i32 {
+    let (from, _) = move_;
+    let color = if board[from] > 0 { Color::White } else { Color::Black };
+    let mut after = *board;
+    make_move(&mut after, move_, CastlingRights::NONE);
+    let check_bonus = if king_attacked(&after, their_king, get_opponent(color)) { CHECK_BONUS } else { 0 };
+    score_move(board, move_) + check_bonus
+}
+
+// score_move_with_check for order_moves, with the king the checks are aimed at
+fn with_check(their_king: Option<Square>) -> impl Fn(&[[i8; 8]; 8], Move) -> i32 {
+    move |board, move_| score_move_with_check(board, move_, their_king)
+}
+
+// Highest score first. Ties go by the squares as seen from the mover's side,
+// so a position and its color-mirrored twin search their moves in the same
+// order and the pruning decisions come out the same.
+fn order_moves(board: &[[i8; 8]; 8], moves: &mut [Move], score: impl Fn(&[[i8; 8]; 8], Move) -> i32) {
+    moves.sort_by_cached_key(|&move_| {
+        let (from, to) = move_;
+        let relative = |square: Square| if board[from] > 0 { square } else { square.flip() };
+        (std::cmp::Reverse(score(board, move_)), relative(from), relative(to))
+    });
+}
 
-    board[to_r][to_f] = piece;
-    board[from_r][from_f] = E;
+// Where each side's pieces stand, for the search to carry alongside its board
+// so check detection and move generation go straight to them instead of
+// scanning all 64 squares. Built once per search by new and kept in step by
+// its make_move and undo_move.
+#[derive(Clone, Copy)]
+pub(crate) struct PieceSquares {
+    // Per color (white first), in the order new found them, as far as captures
+    // and their undoing leave it
+    squares: [[Square; 64]; 2],
+    len: [usize; 2],
+    // The first king of each color new found, if any
+    kings: [Option<Square>; 2],
+}
+
+fn color_index(color: Color) -> usize {
+    if color == Color::White { 0 } else { 1 }
+}
+
+impl PieceSquares {
+    pub(crate) fn new(board: &[[i8; 8]; 8]) -> PieceSquares {
+        let mut pieces = PieceSquares {
+            squares: [[Square::new(0, 0); 64]; 2],
+            len: [0; 2],
+            kings: [None; 2],
+        };
+        for square in Square::all() {
+            let piece = board[square];
+            if piece == E {
+                continue;
+            }
+            let side = color_index(get_piece_color(piece));
+            pieces.squares[side][pieces.len[side]] = square;
+            pieces.len[side] += 1;
+            if piece.abs() == WK && pieces.kings[side].is_none() {
+                pieces.kings[side] = Some(square);
+            }
+        }
+        pieces
+    }
+
+    pub(crate) fn of(&self, color: Color) -> &[Square] {
+        let side = color_index(color);
+        &self.squares[side][..self.len[side]]
+    }
+
+    pub(crate) fn king(&self, color: Color) -> Option<Square> {
+        self.kings[color_index(color)]
+    }
+
+    fn relocate(&mut self, side: usize, from: Square, to: Square) {
+        if let Some(square) = self.squares[side][..self.len[side]].iter_mut().find(|square| **square == from) {
+            *square = to;
+        }
+        if self.kings[side] == Some(from) {
+            self.kings[side] = Some(to);
+        }
+    }
+
+    // The engine's make_move, with these squares following along
+    pub(crate) fn make_move(&mut self, board: &mut [[i8; 8]; 8], move_: Move, current_rights: CastlingRights) -> (i8, CastlingRights) {
+        let (from, to) = move_;
+        let side = color_index(get_piece_color(board[from]));
+        if board[to] != E {
+            let other = 1 - side;
+            let len = self.len[other];
+            if let Some(index) = self.squares[other][..len].iter().position(|&square| square == to) {
+                self.squares[other].swap(index, len - 1);
+                self.len[other] -= 1;
+            }
+        }
+        self.relocate(side, from, to);
+        if board[from].abs() == WK && from.file().abs_diff(to.file()) == 2 {
+            let (rook_from, rook_to) = if to.file() == 6 { (7, 5) } else { (0, 3) };
+            self.relocate(side, Square::new(from.rank(), rook_from), Square::new(from.rank(), rook_to));
+        }
+        make_move(board, move_, current_rights)
+    }
+
+    // The engine's undo_move, putting a captured piece back at the end of its list
+    pub(crate) fn undo_move(&mut self, board: &mut [[i8; 8]; 8], move_: Move, captured: i8) {
+        let (from, to) = move_;
+        let side = color_index(get_piece_color(board[to]));
+        self.relocate(side, to, from);
+        if board[to].abs() == WK && from.file().abs_diff(to.file()) == 2 {
+            let (rook_from, rook_to) = if to.file() == 6 { (7, 5) } else { (0, 3) };
+            self.relocate(side, Square::new(from.rank(), rook_to), Square::new(from.rank(), rook_from));
+        }
+        if captured != E {
+            let other = 1 - side;
+            self.squares[other][self.len[other]] = to;
+            self.len[other] += 1;
+        }
+        undo_move(board, move_, captured);
+    }
+}
+
+pub fn make_move(board: &mut [[i8; 8]; 8], move_: Move, current_rights: CastlingRights) -> (i8, CastlingRights) {
+    let (from, to) = move_;
+    let (from_r, from_f) = (from.rank(), from.file());
+    let (to_r, to_f) = (to.rank(), to.file());
+    let piece = board[from];
+    let captured = board[to];
+
+    board[to] = piece;
+    board[from] = E;
 
     let mut new_rights = current_rights;
 
@@ -82,26 +609,26 @@ pub fn make_move(
     // Update Rights
     // 1. If King moves, lose all rights for that color
     if piece == WK {
-        new_rights &= !(CASTLE_WK | CASTLE_WQ);
+        new_rights = new_rights.without(CASTLE_WK | CASTLE_WQ);
     } else if piece == BK {
-        new_rights &= !(CASTLE_BK | CASTLE_BQ);
+        new_rights = new_rights.without(CASTLE_BK | CASTLE_BQ);
     }
 
     // 2. If Rook moves, lose right for that side
     // White Rooks
     if piece == WR {
         if from_r == 7 && from_f == 0 {
-            new_rights &= !CASTLE_WQ;
+            new_rights = new_rights.without(CASTLE_WQ);
         } else if from_r == 7 && from_f == 7 {
-            new_rights &= !CASTLE_WK;
+            new_rights = new_rights.without(CASTLE_WK);
         }
     }
     // Black Rooks
     if piece == BR {
         if from_r == 0 && from_f == 0 {
-            new_rights &= !CASTLE_BQ;
+            new_rights = new_rights.without(CASTLE_BQ);
         } else if from_r == 0 && from_f == 7 {
-            new_rights &= !CASTLE_BK;
+            new_rights = new_rights.without(CASTLE_BK);
         }
     }
 
@@ -109,36 +636,33 @@ pub fn make_move(
     // If captured was a Rook at original position
     if captured == WR {
         if to_r == 7 && to_f == 0 {
-            new_rights &= !CASTLE_WQ;
+            new_rights = new_rights.without(CASTLE_WQ);
         } else if to_r == 7 && to_f == 7 {
-            new_rights &= !CASTLE_WK;
+            new_rights = new_rights.without(CASTLE_WK);
         }
     } else if captured == BR {
         if to_r == 0 && to_f == 0 {
-            new_rights &= !CASTLE_BQ;
+            new_rights = new_rights.without(CASTLE_BQ);
         } else if to_r == 0 && to_f == 7 {
-            new_rights &= !CASTLE_BK;
+            new_rights = new_rights.without(CASTLE_BK);
         }
     }
 
     (captured, new_rights)
 }
 
-pub fn undo_move(
-    board: &mut [[i8; 8]; 8],
-    move_: ((usize, usize), (usize, usize)),
-    captured: i8,
-) {
-    let ((from_r, from_f), (to_r, to_f)) = move_;
-    
+pub fn undo_move(board: &mut [[i8; 8]; 8], move_: Move, captured: i8) {
+    let (from, to) = move_;
+    let (from_r, to_f) = (from.rank(), to.file());
+
     // Check if it was castling (moved piece is King and dist 2)
-    // Note: board[to_r][to_f] is the piece that moved (King)
-    let piece = board[to_r][to_f];
-    let is_castling = (piece == WK || piece == BK) && (from_f as isize - to_f as isize).abs() == 2;
+    // Note: board[to] is the piece that moved (King)
+    let piece = board[to];
+    let is_castling = (piece == WK || piece == BK) && from.file().abs_diff(to_f) == 2;
 
     // Restore piece
-    board[from_r][from_f] = piece;
-    board[to_r][to_f] = captured;
+    board[from] = piece;
+    board[to] = captured;
 
     if is_castling {
         // Unmove Rook
@@ -156,132 +680,184 @@ pub fn undo_move(
     }
 }
 
-pub fn is_square_attacked(
-    board: &[[i8; 8]; 8],
-    position: (usize, usize),
-    attacker_color: Color,
-) -> bool {
-    for r in 0..8 {
-        for f in 0..8 {
-            let piece = board[r][f];
-            if piece == E {
-                continue;
-            }
-            // Check if piece belongs to attacker
-            let is_white = piece > 0;
-            let piece_color = if is_white { Color::White } else { Color::Black };
-
-            if piece_color == attacker_color {
-                let moves = get_pseudo_legal_moves_for_piece(board, attacker_color, (r, f));
-                if moves.contains(&position) {
-                    return true;
-                }
+// Looks outward from the square (pawn and knight spots, adjacent king, the
+// first piece along each ray) instead of generating every enemy piece's moves
+pub fn is_square_attacked(board: &[[i8; 8]; 8], position: Square, attacker_color: Color) -> bool {
+    let (rank, file) = (position.rank(), position.file());
+    // Attacker's pieces come out positive, defenders negative, empty 0
+    let sign = if attacker_color == Color::White { 1 } else { -1 };
+    let piece_at = |square: Square| board[square] * sign;
+
+    // White pawns capture towards row 0, so they sit one row below the square
+    if [-1, 1].into_iter().filter_map(|df| position.offset(sign as isize, df)).any(|square| piece_at(square) == WP) {
+        return true;
+    }
+    if KNIGHT_TARGETS[rank][file].as_slice().iter().any(|&square| piece_at(square) == WN) {
+        return true;
+    }
+    if KING_TARGETS[rank][file].as_slice().iter().any(|&square| piece_at(square) == WK) {
+        return true;
+    }
+
+    for (directions, slider) in [(ORTHOGONAL, WR), (DIAGONAL, WB)] {
+        for direction in directions {
+            let blocker = RAYS[rank][file][direction].as_slice().iter().map(|&square| piece_at(square)).find(|&piece| piece != E);
+            if blocker == Some(slider) || blocker == Some(WQ) {
+                return true;
             }
         }
     }
     false
 }
 
+pub fn find_king(board: &[[i8; 8]; 8], color: Color) -> Option<Square> {
+    let king = if color == Color::White { WK } else { BK };
+    Square::all().find(|&square| board[square] == king)
+}
+
 pub fn is_in_check(board: &[[i8; 8]; 8], color: Color) -> bool {
-    let king_val = match color {
-        Color::White => WK,
-        Color::Black => BK,
-    };
+    king_attacked(board, find_king(board, color), color)
+}
 
-    let mut king_pos = None;
-    for r in 0..8 {
-        for f in 0..8 {
-            if board[r][f] == king_val {
-                king_pos = Some((r, f));
-                break;
+fn king_attacked(board: &[[i8; 8]; 8], king: Option<Square>, color: Color) -> bool {
+    match king {
+        Some(pos) => is_square_attacked(board, pos, get_opponent(color)),
+        None => true, // Should not happen, but if no king, yes we are in "check"?
+    }
+}
+
+// is_in_check for a board whose pieces are already known
+fn in_check(board: &[[i8; 8]; 8], pieces: &PieceSquares, color: Color) -> bool {
+    king_attacked(board, pieces.king(color), color)
+}
+
+// Every square `attacker_color` attacks, whatever stands on it. Built once per
+// get_legal_moves call instead of asking is_square_attacked square by square
+pub(crate) fn attack_map(board: &[[i8; 8]; 8], attacker_color: Color) -> [[bool; 8]; 8] {
+    let mut map = [[false; 8]; 8];
+    let sign = if attacker_color == Color::White { 1 } else { -1 };
+
+    for square in Square::all() {
+        let (r, f) = (square.rank(), square.file());
+        let (targets, directions): (&[Square], &[usize]) = match board[square] * sign {
+            WP => {
+                for target in [-1, 1].into_iter().filter_map(|df| square.offset(-sign as isize, df)) {
+                    map[target] = true;
+                }
+                continue;
             }
+            WN => (KNIGHT_TARGETS[r][f].as_slice(), &[]),
+            WK => (KING_TARGETS[r][f].as_slice(), &[]),
+            WR => (&[], &ORTHOGONAL),
+            WB => (&[], &DIAGONAL),
+            WQ => (&[], &ALL_DIRECTIONS),
+            _ => continue,
+        };
+        for &target in targets {
+            map[target] = true;
         }
-        if king_pos.is_some() {
-            break;
+        for &direction in directions {
+            for &target in RAYS[r][f][direction].as_slice() {
+                map[target] = true;
+                if board[target] != E {
+                    break;
+                }
+            }
         }
     }
-
-    match king_pos {
-        Some(pos) => is_square_attacked(board, pos, get_opponent(color)),
-        None => true, // Should not happen, but if no king, yes we are in "check"?
-    }
+    map
 }
 
 pub fn get_legal_moves(
     board: &[[i8; 8]; 8],
     color: Color,
-    castling_rights: u8,
-) -> Vec<((usize, usize), (usize, usize))> {
-    let pseudo_moves = get_all_pseudo_legal_moves(board, color);
-    let mut legal_moves = Vec::new();
+    castling_rights: CastlingRights,
+) -> Vec<Move> {
+    legal_moves(board, &PieceSquares::new(board), color, castling_rights)
+}
 
-    let mut board_clone = *board;
+// get_legal_moves for a board whose pieces are already known
+pub(crate) fn legal_moves(
+    board: &[[i8; 8]; 8],
+    pieces: &PieceSquares,
+    color: Color,
+    castling_rights: CastlingRights,
+) -> Vec<Move> {
+    let Some(king_square) = pieces.king(color) else {
+        return Vec::new();
+    };
+    let opponent = get_opponent(color);
+
+    // Built with our king lifted off the board, so squares behind it along a
+    // checking ray count as attacked too. The same copy then serves for the
+    // make/undo checks below.
+    let king_piece = if color == Color::White { WK } else { BK };
+    let mut scratch = *board;
+    scratch[king_square] = E;
+    let attacked = attack_map(&scratch, opponent);
+    scratch[king_square] = king_piece;
+    let in_check = attacked[king_square];
+
+    let pseudo_moves = get_all_pseudo_legal_moves(board, color, pieces.of(color));
+    let mut legal_moves = Vec::with_capacity(pseudo_moves.len() + 2);
 
     // Normal pseudo moves
     for move_ in pseudo_moves {
-        let (captured, _) = make_move(&mut board_clone, move_, castling_rights);
-        if !is_in_check(&board_clone, color) {
+        let (from, to) = move_;
+        if from == king_square {
+            if !attacked[to] {
+                legal_moves.push(move_);
+            }
+            continue;
+        }
+
+        // Out of check, a piece off the king's lines can't expose it
+        let dr = from.rank() as isize - king_square.rank() as isize;
+        let df = from.file() as isize - king_square.file() as isize;
+        if !in_check && dr != 0 && df != 0 && dr.abs() != df.abs() {
             legal_moves.push(move_);
+            continue;
         }
-        undo_move(&mut board_clone, move_, captured);
+
+        let (captured, _) = make_move(&mut scratch, move_, castling_rights);
+        if !is_square_attacked(&scratch, king_square, opponent) {
+            legal_moves.push(move_);
+        }
+        undo_move(&mut scratch, move_, captured);
     }
 
     // Castling Logic
-    if !is_in_check(board, color) {
-        let (rank, king_mask, queen_mask, k_side_sqs, q_side_sqs) = match color {
-            Color::White => (
-                7,
-                CASTLE_WK,
-                CASTLE_WQ,
-                vec![5, 6],    // Empty for KS: f1, g1
-                vec![1, 2, 3], // Empty for QS: b1, c1, d1
-            ),
-            Color::Black => (
-                0,
-                CASTLE_BK,
-                CASTLE_BQ,
-                vec![5, 6],    // Empty for KS: f8, g8
-                vec![1, 2, 3], // Empty for QS: b8, c8, d8
-            ),
+    if !in_check {
+        // Files that must be empty: f and g kingside, b, c and d queenside
+        const KING_SIDE_FILES: [usize; 2] = [5, 6];
+        const QUEEN_SIDE_FILES: [usize; 3] = [1, 2, 3];
+        let (rank, king_mask, queen_mask) = match color {
+            Color::White => (7, CASTLE_WK, CASTLE_WQ),
+            Color::Black => (0, CASTLE_BK, CASTLE_BQ),
         };
 
         // Safety: Check if King is actually on the board at start pos
         // (Prevents phantom castling if rights are desynced)
-        let king_piece = if color == Color::White { WK } else { BK };
         if board[rank][4] == king_piece {
             // Kingside
-            if (castling_rights & king_mask) != 0 {
-                let mut clear = true;
-                for &f in &k_side_sqs {
-                    if board[rank][f] != E {
-                        clear = false;
-                        break;
-                    }
-                }
-                if clear {
-                    if !is_square_attacked(board, (rank, 5), get_opponent(color))
-                        && !is_square_attacked(board, (rank, 6), get_opponent(color))
-                    {
-                        legal_moves.push(((rank, 4), (rank, 6)));
-                    }
+            if castling_rights.has(king_mask) {
+                let clear = KING_SIDE_FILES.iter().all(|&f| board[rank][f] == E);
+                if clear
+                    && !attacked[rank][5]
+                    && !attacked[rank][6]
+                {
+                    legal_moves.push((Square::new(rank, 4), Square::new(rank, 6)));
                 }
             }
 
             // Queenside
-            if (castling_rights & queen_mask) != 0 {
-                let mut clear = true;
-                for &f in &q_side_sqs {
-                    if board[rank][f] != E {
-                        clear = false;
-                        break;
-                    }
-                }
-                if clear {
-                    if !is_square_attacked(board, (rank, 3), get_opponent(color))
-                        && !is_square_attacked(board, (rank, 2), get_opponent(color))
-                    {
-                        legal_moves.push(((rank, 4), (rank, 2)));
-                    }
+            if castling_rights.has(queen_mask) {
+                let clear = QUEEN_SIDE_FILES.iter().all(|&f| board[rank][f] == E);
+                if clear
+                    && !attacked[rank][3]
+                    && !attacked[rank][2]
+                {
+                    legal_moves.push((Square::new(rank, 4), Square::new(rank, 2)));
                 }
             }
         }
@@ -290,146 +866,1273 @@ pub fn get_legal_moves(
     legal_moves
 }
 
-fn is_maximizing(color: Color) -> bool {
-    color == Color::White
-}
+// Quiescence plies (qdepth) within which a side in check gets every evasion
+// searched; deeper than this it stands pat like anyone else, so a long run of
+// checks can't keep the search going
+const QSEARCH_CHECK_PLIES: i32 = 4;
 
-pub fn minimax(
+// Captures-only search from a quiet-looking node, so the score it hands back
+// isn't in the middle of an exchange. The side to move may "stand pat" on the
+// static eval instead of capturing, except in check, where every evasion is
+// tried. qdepth counts the plies since the main search handed over.
+#[allow(clippy::too_many_arguments)]
+fn quiescence(
     board: &mut [[i8; 8]; 8],
+    pieces: &mut PieceSquares,
     color: Color,
-    depth: i32,
+    ply: i32,
+    qdepth: i32,
     mut alpha: i32,
-    mut beta: i32,
-    castling_rights: u8,
-    use_pruning: bool,
-    use_move_ordering: bool,
+    beta: i32,
+    castling_rights: CastlingRights,
     eval_count: &mut u32,
 ) -> i32 {
-    if depth == 0 {
-        *eval_count += 1;
-        return evaluate_board(board);
+    note_ply(ply);
+    record(|stats| stats.qnodes += 1);
+    if count_node() {
+        return 0;
     }
 
-    let mut legal_moves = get_legal_moves(board, color, castling_rights);
-    
-    if use_move_ordering {
-        legal_moves.sort_by(|a, b| {
-            let score_a = score_move(board, *a);
-            let score_b = score_move(board, *b);
-            score_b.cmp(&score_a) // Descending
-        });
+    // The per-ply tables end here
+    if ply >= MAX_PLY as i32 {
+        *eval_count += 1;
+        return evaluate(board, color);
     }
 
-    if legal_moves.is_empty() {
-        if is_in_check(board, color) {
-            // Checkmate
-            if color == Color::White {
-                return -10000 - depth;
-            } else {
-                return 10000 + depth;
-            }
+    // Past QSEARCH_CHECK_PLIES a side in check is treated as if it weren't
+    let evading = qdepth < QSEARCH_CHECK_PLIES && in_check(board, pieces, color);
+    let mut best_point = i32::MIN;
+    let mut stand_pat = i32::MIN;
+    if !evading {
+        *eval_count += 1;
+        stand_pat = evaluate(board, color);
+        if stand_pat >= beta {
+            return stand_pat;
         }
-        // Stalemate
-        return 0;
+        best_point = stand_pat;
+        alpha = alpha.max(stand_pat);
     }
 
-    let maximizing = is_maximizing(color);
-    let mut best_point = if maximizing { i32::MIN } else { i32::MAX };
+    let mut moves = legal_moves(board, pieces, color, castling_rights);
+    if moves.is_empty() && evading {
+        return -(MATE_SCORE - ply);
+    }
+    if !evading {
+        moves.retain(|&(_, to)| board[to] != E);
+    }
+    order_moves(board, &mut moves, with_check(pieces.king(get_opponent(color))));
 
-    for move_ in legal_moves {
-        let (captured, new_rights) = make_move(board, move_, castling_rights);
-        let point = minimax(board, get_opponent(color), depth - 1, alpha, beta, new_rights, use_pruning, use_move_ordering, eval_count);
-        undo_move(board, move_, captured);
+    for move_ in moves {
+        // Delta pruning: a capture that can't lift the stand-pat score to
+        // alpha even with a margin on top isn't worth following. The most it
+        // could reach still bounds the score handed back, as with futility
+        // pruning, or a parent would take stand-pat as the whole story.
+        let (_, to) = move_;
+        let gain = get_piece_value(board[to]).abs() * PAWN_VALUE;
+        if !evading && stand_pat + gain + DELTA_MARGIN <= alpha {
+            record(|stats| stats.delta_skips += 1);
+            best_point = best_point.max(stand_pat + gain + DELTA_MARGIN);
+            continue;
+        }
 
-        if maximizing {
-            best_point = best_point.max(point);
-            alpha = alpha.max(point);
-            if use_pruning && beta <= alpha {
-                break;
-            }
-        } else {
-            best_point = best_point.min(point);
-            beta = beta.min(point);
-            if use_pruning && beta <= alpha {
-                break;
-            }
+        let (captured, new_rights) = pieces.make_move(board, move_, castling_rights);
+        let point = -quiescence(board, pieces, get_opponent(color), ply + 1, qdepth + 1, -beta, -alpha, new_rights, eval_count);
+        pieces.undo_move(board, move_, captured);
+
+        best_point = best_point.max(point);
+        alpha = alpha.max(point);
+        if alpha >= beta {
+            break;
         }
     }
     best_point
 }
 
-pub fn get_best_move(
-    board: &[[i8; 8]; 8],
+// Negamax with alpha-beta: scores are from the point of view of `color`, the
+// side to move, and each ply just negates its children's scores. Mates are
+// scored by their distance from this call (see MATE_SCORE).
+#[allow(clippy::too_many_arguments)]
+pub fn negamax(
+    board: &mut [[i8; 8]; 8],
     color: Color,
     depth: i32,
-    castling_rights: u8,
+    alpha: i32,
+    beta: i32,
+    castling_rights: CastlingRights,
     use_pruning: bool,
     use_move_ordering: bool,
-) -> Option<((usize, usize), (usize, usize), u32)> {
-    // We need a mutable board for minimax
-    let mut board_clone = *board;
-    let mut legal_moves = get_legal_moves(&board_clone, color, castling_rights);
+    eval_count: &mut u32,
+) -> i32 {
+    let mut pieces = PieceSquares::new(board);
+    search(board, &mut pieces, color, depth, 0, alpha, beta, castling_rights, root_halfmove_clock(), use_pruning, use_move_ordering, eval_count)
+}
 
-    if use_move_ordering {
-        legal_moves.sort_by(|a, b| {
-            let score_a = score_move(board, *a);
-            let score_b = score_move(board, *b);
-            score_b.cmp(&score_a)
-        });
-    }
+#[allow(clippy::too_many_arguments)]
+fn search(
+    board: &mut [[i8; 8]; 8],
+    pieces: &mut PieceSquares,
+    color: Color,
+    depth: i32,
+    ply: i32,
+    mut alpha: i32,
+    mut beta: i32,
+    castling_rights: CastlingRights,
+    halfmove_clock: u32,
+    use_pruning: bool,
+    use_move_ordering: bool,
+    eval_count: &mut u32,
+) -> i32 {
+    pv_clear(ply);
+    note_ply(ply);
+    record(|stats| stats.nodes += 1);
+
+    // Mate distance pruning: nothing below here can beat mating sooner than
+    // we can from here, or be worse than being mated right now
+    if use_pruning && ply > 0 {
+        alpha = alpha.max(-(MATE_SCORE - ply));
+        beta = beta.min(MATE_SCORE - ply - 1);
+        if alpha >= beta {
+            return alpha;
+        }
+    }
+
+    if count_node() {
+        return 0;
+    }
+
+    // Twofold repetition is enough inside the tree: whoever steered back here
+    // can keep doing it, so the line is a draw
+    let hash = zobrist_hash(board, color, castling_rights);
+    if ply > 0 && is_repetition(hash) {
+        return 0;
+    }
+
+    // Fifty moves without a capture or pawn move is a draw too, unless the
+    // move that got here mated
+    if ply > 0
+        && halfmove_clock >= 100
+        && (!in_check(board, pieces, color) || !legal_moves(board, pieces, color, castling_rights).is_empty())
+    {
+        return 0;
+    }
+
+    // A root searched at depth 0 reaches here below zero; that's a leaf too
+    if depth <= 0 {
+        // With a big material lead the obvious danger past the horizon is
+        // leaving the losing side without moves, so look before trusting the eval
+        if evaluate_board(board).abs() >= STALEMATE_CHECK_MARGIN
+            && legal_moves(board, pieces, color, castling_rights).is_empty()
+        {
+            return if in_check(board, pieces, color) { -(MATE_SCORE - ply) } else { 0 };
+        }
+        // Settle any exchange in progress before trusting a score here
+        return quiescence(board, pieces, color, ply, 0, alpha, beta, castling_rights, eval_count);
+    }
+
+    // Static eval for the shallow-depth pruning below, which never applies in
+    // check (every evasion matters) or with mate scores in the window
+    let preset = current_preset();
+    let static_eval = (use_pruning
+        && depth >= 0
+        && depth < preset.reverse_futility_margins.len() as i32
+        && alpha.abs() < MATE_BOUND
+        && beta.abs() < MATE_BOUND
+        && !in_check(board, pieces, color))
+    .then(|| evaluate(board, color));
+
+    // Reverse futility pruning: so far above beta that even giving back a
+    // margin per remaining ply leaves it there, the opponent won't allow this.
+    // Done before move generation: a side not in check can't be mated on the
+    // spot, and being stalemated while this far ahead is rare.
+    if let Some(eval) = static_eval {
+        let margin = preset.reverse_futility_margins[depth as usize];
+        if eval - margin >= beta {
+            record(|stats| stats.reverse_futility_cutoffs += 1);
+            return eval - margin;
+        }
+    }
+
+    // Razoring: this far below alpha near the horizon, only a tactic could
+    // help, so ask quiescence. A fail-low there is trusted; anything else
+    // gets the full search after all.
+    if let Some(eval) = static_eval.filter(|_| depth >= 0 && depth < preset.razor_margins.len() as i32) {
+        if eval + preset.razor_margins[depth as usize] < alpha {
+            let point = quiescence(board, pieces, color, ply, 0, alpha - 1, alpha, castling_rights, eval_count);
+            if point < alpha {
+                record(|stats| stats.razor_cutoffs += 1);
+                return point;
+            }
+        }
+    }
+
+    let mut legal_moves = legal_moves(board, pieces, color, castling_rights);
+
+    if use_move_ordering {
+        if beta - alpha > 1 {
+            order_moves(board, &mut legal_moves, with_check(pieces.king(get_opponent(color))));
+        } else {
+            order_moves(board, &mut legal_moves, score_move);
+        }
+    }
+
+    if legal_moves.is_empty() {
+        // Checkmate (sooner is worse for the mated side), otherwise stalemate
+        return if in_check(board, pieces, color) { -(MATE_SCORE - ply) } else { 0 };
+    }
+
+    history_push(hash);
+
+    // Internal iterative deepening: with no hash move to try first, a PV node
+    // deep enough to matter gets a cheap reduced-depth search to pick one, since
+    // PVS depends on the first move being the best
+    if use_move_ordering && depth >= IID_MIN_DEPTH && beta - alpha > 1 && legal_moves.len() > 1 {
+        let mut best = (i32::MIN, 0);
+        let mut iid_alpha = alpha;
+        for (i, &move_) in legal_moves.iter().enumerate() {
+            let (captured, new_rights) = pieces.make_move(board, move_, castling_rights);
+            let clock = next_halfmove_clock(board, move_, captured, halfmove_clock);
+            let point = -search(board, pieces, get_opponent(color), depth - 1 - IID_REDUCTION, ply + 1, -beta, -iid_alpha, new_rights, clock, use_pruning, use_move_ordering, eval_count);
+            pieces.undo_move(board, move_, captured);
+            if point > best.0 {
+                best = (point, i);
+            }
+            iid_alpha = iid_alpha.max(point);
+            if use_pruning && iid_alpha >= beta {
+                break;
+            }
+        }
+        legal_moves[..=best.1].rotate_right(1);
+    }
+
+    // Futility pruning: when even a generous margin on top of the static eval
+    // can't reach alpha, only captures and checks are worth searching
+    let futility_bound = static_eval
+        .filter(|_| depth >= 0 && depth < preset.futility_margins.len() as i32)
+        .map(|eval| eval + preset.futility_margins[depth as usize])
+        .filter(|&bound| bound <= alpha);
+
+    let mut best_point = i32::MIN;
+    for (i, move_) in legal_moves.into_iter().enumerate() {
+        let (captured, new_rights) = pieces.make_move(board, move_, castling_rights);
+        let opponent = get_opponent(color);
+        if let Some(bound) = futility_bound {
+            if i > 0 && captured == E && !in_check(board, pieces, opponent) {
+                record(|stats| stats.futility_skips += 1);
+                pieces.undo_move(board, move_, captured);
+                best_point = best_point.max(bound);
+                continue;
+            }
+        }
+        let clock = next_halfmove_clock(board, move_, captured, halfmove_clock);
+        // Principal variation search: the first (best ordered) move gets the
+        // full window, the rest only have to prove they're no better with a
+        // zero window, and are searched again if one turns out to be
+        let point = if use_pruning && i > 0 {
+            let point = -search(board, pieces, opponent, depth - 1, ply + 1, -alpha - 1, -alpha, new_rights, clock, use_pruning, use_move_ordering, eval_count);
+            if point > alpha && point < beta {
+                -search(board, pieces, opponent, depth - 1, ply + 1, -beta, -alpha, new_rights, clock, use_pruning, use_move_ordering, eval_count)
+            } else {
+                point
+            }
+        } else {
+            -search(board, pieces, opponent, depth - 1, ply + 1, -beta, -alpha, new_rights, clock, use_pruning, use_move_ordering, eval_count)
+        };
+        pieces.undo_move(board, move_, captured);
+
+        if point > alpha {
+            pv_update(ply, move_);
+        }
+        best_point = best_point.max(point);
+        alpha = alpha.max(point);
+        if use_pruning && alpha >= beta {
+            record(|stats| stats.beta_cutoffs[i.min(CUTOFF_BUCKETS - 1)] += 1);
+            break;
+        }
+    }
+    history_pop();
+    best_point
+}
+
+// White-positive wrapper around negamax for callers (the wasm exports, the
+// CLI, analysis tools) that show scores from White's side. alpha and beta are
+// white-positive too.
+#[allow(clippy::too_many_arguments)]
+pub fn minimax(
+    board: &mut [[i8; 8]; 8],
+    color: Color,
+    depth: i32,
+    alpha: i32,
+    beta: i32,
+    castling_rights: CastlingRights,
+    use_pruning: bool,
+    use_move_ordering: bool,
+    eval_count: &mut u32,
+) -> i32 {
+    if color == Color::White {
+        negamax(board, color, depth, alpha, beta, castling_rights, use_pruning, use_move_ordering, eval_count)
+    } else {
+        -negamax(board, color, depth, -beta, -alpha, castling_rights, use_pruning, use_move_ordering, eval_count)
+    }
+}
+
+pub fn get_best_move(
+    board: &[[i8; 8]; 8],
+    color: Color,
+    depth: i32,
+    castling_rights: CastlingRights,
+    use_pruning: bool,
+    use_move_ordering: bool,
+    avoid_position: Option<&[[i8; 8]; 8]>,
+) -> Option<(Square, Square, u32)> {
+    get_best_line(board, color, depth, castling_rights, use_pruning, use_move_ordering, avoid_position)
+        .map(|line| (line.pv[0].0, line.pv[0].1, line.eval_count))
+}
+
+// The chosen root move together with the line the search expects to follow it
+#[derive(Clone, Debug)]
+pub struct BestLine {
+    // Starts with the move to play; never empty
+    pub pv: Vec<Move>,
+    // From the side to move's point of view
+    pub score: i32,
+    pub eval_count: u32,
+    pub depth: i32,
+    // Deepest ply reached below the root, quiescence included
+    pub seldepth: i32,
+}
+
+// get_best_move with the principal variation and score of the chosen move
+pub fn get_best_line(
+    board: &[[i8; 8]; 8],
+    color: Color,
+    depth: i32,
+    castling_rights: CastlingRights,
+    use_pruning: bool,
+    use_move_ordering: bool,
+    avoid_position: Option<&[[i8; 8]; 8]>,
+) -> Option<BestLine> {
+    // We need a mutable board for minimax
+    let mut board_clone = *board;
+    let mut legal_moves = get_legal_moves(&board_clone, color, castling_rights);
+
+    // Casual play: don't step straight back into the position we just left,
+    // unless every legal move does exactly that
+    if let Some(previous) = avoid_position {
+        let fresh_moves: Vec<_> = legal_moves
+            .iter()
+            .copied()
+            .filter(|&move_| {
+                let (captured, _) = make_move(&mut board_clone, move_, castling_rights);
+                let repeats = board_clone == *previous;
+                undo_move(&mut board_clone, move_, captured);
+                !repeats
+            })
+            .collect();
+        if !fresh_moves.is_empty() {
+            legal_moves = fresh_moves;
+        }
+    }
+
+    if use_move_ordering {
+        order_moves(board, &mut legal_moves, with_check(find_king(board, get_opponent(color))));
+    }
 
     if legal_moves.is_empty() {
         return None;
     }
 
-    let mut points_w_moves = Vec::new();
-    let maximizing = is_maximizing(color);
+    let mut lines = search_root_moves(&mut board_clone, color, depth, castling_rights, use_pruning, use_move_ordering, &legal_moves);
+    let points_w_moves: Vec<_> = lines.iter().map(|line| (line.score, line.pv[0], line.eval_count)).collect();
+    let seldepth = lines.iter().map(|line| line.seldepth).max().unwrap_or(0);
+
+    let (from, to, eval_count) = choose_best(points_w_moves)?;
+    let index = lines.iter().position(|line| line.pv[0] == (from, to))?;
+    let mut best = lines.swap_remove(index);
+    best.eval_count = eval_count;
+    best.seldepth = seldepth;
+    Some(best)
+}
+
+// Each root move searched with the full window, so every score is exact
+fn search_root_moves(
+    board: &mut [[i8; 8]; 8],
+    color: Color,
+    depth: i32,
+    castling_rights: CastlingRights,
+    use_pruning: bool,
+    use_move_ordering: bool,
+    moves: &[Move],
+) -> Vec<BestLine> {
+    // Root moves don't share anything, so native builds (and wasm-threads
+    // builds) spread them over the cores, each with its own board. Budgeted
+    // searches keep their stop conditions in this thread's LIMITS, and
+    // with_stats its counters in STATS, so those stay on this thread. The
+    // preset and game history are copied over to the worker threads.
+    #[cfg(any(not(target_arch = "wasm32"), feature = "wasm-threads"))]
+    if !limits_active() && !stats_active() {
+        use rayon::prelude::*;
+        let board = *board;
+        let history = HISTORY.with(|cell| cell.borrow().clone());
+        let halfmove_clock = root_halfmove_clock();
+        let preset = current_preset();
+        return moves
+            .par_iter()
+            .map(|&move_| {
+                let mut board = board;
+                with_preset(preset, || {
+                    with_game_history(&history, halfmove_clock, || {
+                        search_root_move(&mut board, color, depth, castling_rights, use_pruning, use_move_ordering, move_)
+                    })
+                })
+            })
+            .collect();
+    }
+
+    moves
+        .iter()
+        .map(|&move_| search_root_move(board, color, depth, castling_rights, use_pruning, use_move_ordering, move_))
+        .collect()
+}
+
+fn search_root_move(
+    board: &mut [[i8; 8]; 8],
+    color: Color,
+    depth: i32,
+    castling_rights: CastlingRights,
+    use_pruning: bool,
+    use_move_ordering: bool,
+    move_: Move,
+) -> BestLine {
+    let mut eval_count = 0;
+    SELDEPTH.with(|cell| cell.set(0));
+    history_push(zobrist_hash(board, color, castling_rights));
+    let mut pieces = PieceSquares::new(board);
+    let (captured, new_rights) = pieces.make_move(board, move_, castling_rights);
+    let point = -search(
+        board,
+        &mut pieces,
+        get_opponent(color),
+        depth - 1,
+        1,
+        -50000,
+        50000,
+        new_rights,
+        next_halfmove_clock(board, move_, captured, root_halfmove_clock()),
+        use_pruning,
+        use_move_ordering,
+        &mut eval_count,
+    );
+    let mut pv = vec![move_];
+    pv.extend(pv_line(1));
+    pieces.undo_move(board, move_, captured);
+    history_pop();
+    BestLine {
+        pv,
+        score: point,
+        eval_count,
+        depth,
+        seldepth: SELDEPTH.with(|cell| cell.get()),
+    }
+}
+
+// MultiPV: the `count` best root moves, best first, each with its own score
+// and line, for the analysis board's candidate arrows. eval_count is per line.
+pub fn get_top_lines(
+    board: &[[i8; 8]; 8],
+    color: Color,
+    depth: i32,
+    castling_rights: CastlingRights,
+    count: usize,
+) -> Vec<BestLine> {
+    let mut board_clone = *board;
+    let mut moves = get_legal_moves(board, color, castling_rights);
+    order_moves(board, &mut moves, with_check(find_king(board, get_opponent(color))));
+
+    let mut lines = search_root_moves(&mut board_clone, color, depth, castling_rights, true, true, &moves);
+    // Stable, so equal scores keep the move ordering
+    lines.sort_by_key(|line| std::cmp::Reverse(line.score));
+    lines.truncate(count);
+    lines
+}
+
+// Casual play: instead of always the best move, each root move is played with
+// a weight of exp((score - best) / temperature), temperature in centipawns.
+// At 50 a move half a pawn worse than the best comes up e^-1 times as often;
+// at 0 this is get_best_line. Eval count covers every root move.
+pub fn get_best_move_temperature(
+    board: &[[i8; 8]; 8],
+    color: Color,
+    depth: i32,
+    castling_rights: CastlingRights,
+    temperature: f64,
+) -> Option<BestLine> {
+    if temperature <= 0.0 {
+        return get_best_line(board, color, depth, castling_rights, true, true, None);
+    }
+
+    let mut lines = get_top_lines(board, color, depth, castling_rights, usize::MAX);
+    let best = lines.first()?.score;
+    let eval_count = lines.iter().map(|line| line.eval_count).sum();
+    let weights: Vec<f64> = lines
+        .iter()
+        .map(|line| ((line.score - best) as f64 / temperature).exp())
+        .collect();
+
+    let mut pick = RNG.with(|rng| rng.borrow_mut().random_range(0.0..weights.iter().sum::<f64>()));
+    let index = weights
+        .iter()
+        .position(|&weight| {
+            pick -= weight;
+            pick < 0.0
+        })
+        .unwrap_or(0);
+    let mut line = lines.swap_remove(index);
+    line.eval_count = eval_count;
+    Some(line)
+}
+
+// Best scored root move, ties broken at random, with the total eval count
+fn choose_best(points_w_moves: Vec<(i32, Move, u32)>) -> Option<(Square, Square, u32)> {
+    if points_w_moves.is_empty() {
+        return None;
+    }
+
+    let total_evals: u32 = points_w_moves.iter().map(|(_, _, c)| c).sum();
+
+    let best_score = points_w_moves.iter().map(|(p, _, _)| *p).max().unwrap();
+
+    let best_moves: Vec<_> = points_w_moves
+        .into_iter()
+        .filter(|(p, _, _)| *p == best_score)
+        .map(|(_, m, _)| m)
+        .collect();
+
+    let best_move = RNG.with(|rng| best_moves.choose(&mut *rng.borrow_mut()).cloned());
+    
+    best_move.map(|m| (m.0, m.1, total_evals))
+}
+
+pub enum SearchStatus {
+    Pending,
+    Complete(Option<(Square, Square, u32)>),
+}
+
+// get_best_move split into slices, so a page without workers can search a
+// little per animation frame. Root moves are searched one at a time until
+// the node budget is spent, so a step can overshoot by one root move's worth.
+pub struct ResumableSearch {
+    board: [[i8; 8]; 8],
+    color: Color,
+    depth: i32,
+    castling_rights: CastlingRights,
+    use_pruning: bool,
+    use_move_ordering: bool,
+    moves: Vec<Move>,
+    points_w_moves: Vec<(i32, Move, u32)>,
+}
+
+impl ResumableSearch {
+    pub fn new(
+        board: &[[i8; 8]; 8],
+        color: Color,
+        depth: i32,
+        castling_rights: CastlingRights,
+        use_pruning: bool,
+        use_move_ordering: bool,
+    ) -> ResumableSearch {
+        let mut moves = get_legal_moves(board, color, castling_rights);
+        if use_move_ordering {
+            order_moves(board, &mut moves, with_check(find_king(board, get_opponent(color))));
+        }
+        // Searched from the back
+        moves.reverse();
+
+        ResumableSearch {
+            board: *board,
+            color,
+            depth,
+            castling_rights,
+            use_pruning,
+            use_move_ordering,
+            moves,
+            points_w_moves: Vec::new(),
+        }
+    }
+
+    // Root moves searched so far and in total, for a progress bar
+    pub fn progress(&self) -> (usize, usize) {
+        let searched = self.points_w_moves.len();
+        (searched, searched + self.moves.len())
+    }
+
+    pub fn step(&mut self, budget_nodes: u32) -> SearchStatus {
+        let mut spent = 0;
+        while spent < budget_nodes {
+            let Some(move_) = self.moves.pop() else {
+                break;
+            };
+            let line = search_root_move(
+                &mut self.board,
+                self.color,
+                self.depth,
+                self.castling_rights,
+                self.use_pruning,
+                self.use_move_ordering,
+                move_,
+            );
+            self.points_w_moves.push((line.score, move_, line.eval_count));
+            spent += line.eval_count.max(1);
+        }
+
+        if self.moves.is_empty() {
+            SearchStatus::Complete(choose_best(self.points_w_moves.clone()))
+        } else {
+            SearchStatus::Pending
+        }
+    }
+
+    // Give up on the root moves not searched yet and settle for the best of
+    // those that were, for a stop button. No move if none was searched.
+    pub fn stop(&mut self) -> Option<(Square, Square, u32)> {
+        self.moves.clear();
+        choose_best(self.points_w_moves.clone())
+    }
+}
+
+// How much a limited search may spend; None means no limit of that kind
+#[derive(Clone, Default, Debug)]
+pub struct SearchBudget {
+    pub movetime_ms: Option<f64>,
+    // Nodes of the whole search, counted the same on every device, for
+    // callers that need the same answer regardless of speed
+    pub max_nodes: Option<u64>,
+    // Stops the search when set, from any thread; the best move found so far
+    // is still returned
+    pub stop: Option<Arc<AtomicBool>>,
+}
+
+// Deepest iteration a limited search will start
+const MAX_LIMITED_DEPTH: i32 = 32;
+
+// Progress of an iterative search, reported after every finished iteration
+pub struct SearchInfo<'a> {
+    pub depth: i32,
+    pub seldepth: i32,
+    // From the side to move's point of view
+    pub score: i32,
+    // All nodes searched so far, across iterations
+    pub nodes: u64,
+    pub elapsed_ms: f64,
+    pub pv: &'a [Move],
+}
+
+impl SearchInfo<'_> {
+    pub fn nps(&self) -> f64 {
+        self.nodes as f64 * 1000.0 / self.elapsed_ms.max(1.0)
+    }
+}
+
+// The one hook for watching a search: the CLI's analyze output and the wasm
+// progress callback are both observers. Closures work too.
+pub trait SearchObserver {
+    fn on_iteration(&mut self, info: &SearchInfo);
+}
 
-    let alpha = -50000;
-    let beta = 50000;
+impl<F: FnMut(&SearchInfo)> SearchObserver for F {
+    fn on_iteration(&mut self, info: &SearchInfo) {
+        self(info)
+    }
+}
+
+// For callers that don't watch
+impl SearchObserver for () {
+    fn on_iteration(&mut self, _: &SearchInfo) {}
+}
+
+// get_best_move under a budget instead of a fixed depth: deepens one ply at a
+// time and plays the best line of the last iteration that finished. Depth 1
+// always completes. eval_count covers all the finished iterations.
+pub fn get_best_move_limited(
+    board: &[[i8; 8]; 8],
+    color: Color,
+    castling_rights: CastlingRights,
+    budget: SearchBudget,
+    use_pruning: bool,
+    use_move_ordering: bool,
+) -> Option<BestLine> {
+    get_best_move_observed(board, color, castling_rights, budget, use_pruning, use_move_ordering, &mut ())
+}
+
+// get_best_move_limited reporting each finished iteration to `observer`
+pub fn get_best_move_observed(
+    board: &[[i8; 8]; 8],
+    color: Color,
+    castling_rights: CastlingRights,
+    budget: SearchBudget,
+    use_pruning: bool,
+    use_move_ordering: bool,
+    observer: &mut dyn SearchObserver,
+) -> Option<BestLine> {
+    let start = now_ms();
+    let report = |observer: &mut dyn SearchObserver, line: &BestLine| {
+        observer.on_iteration(&SearchInfo {
+            depth: line.depth,
+            seldepth: line.seldepth,
+            score: line.score,
+            nodes: nodes_searched(),
+            elapsed_ms: now_ms() - start,
+            pv: &line.pv,
+        })
+    };
+
+    // A cap that never triggers, so depth 1 always completes but still runs
+    // on this thread and its nodes are counted here
+    set_limits(SearchLimits {
+        max_nodes: Some(u64::MAX),
+        ..SearchLimits::default()
+    });
+    let mut best = get_best_line(board, color, 1, castling_rights, use_pruning, use_move_ordering, None)?;
+    let mut total_evals = best.eval_count;
+    report(observer, &best);
+
+    if get_legal_moves(board, color, castling_rights).len() > 1 {
+        set_limits(SearchLimits {
+            deadline_ms: budget.movetime_ms.map(|ms| start + ms),
+            max_nodes: budget.max_nodes,
+            nodes: nodes_searched(),
+            stopped: false,
+        });
+        STOP_TOKEN.with(|token| *token.borrow_mut() = budget.stop.clone());
+        for depth in 2..=MAX_LIMITED_DEPTH {
+            // The next iteration costs several times this one, so past half
+            // the budget it would only be thrown away
+            if budget.movetime_ms.is_some_and(|ms| now_ms() - start > ms / 2.0)
+                || budget.max_nodes.is_some_and(|max| nodes_searched() > max / 2)
+            {
+                break;
+            }
+            let result = get_best_line(board, color, depth, castling_rights, use_pruning, use_move_ordering, None);
+            if search_stopped() {
+                break;
+            }
+            let Some(result) = result else { break };
+            total_evals += result.eval_count;
+            best = result;
+            report(observer, &best);
+        }
+        STOP_TOKEN.with(|token| *token.borrow_mut() = None);
+    }
+    set_limits(SearchLimits::default());
+
+    best.eval_count = total_evals;
+    Some(best)
+}
+
+pub fn get_best_move_timed(
+    board: &[[i8; 8]; 8],
+    color: Color,
+    castling_rights: CastlingRights,
+    movetime_ms: f64,
+    use_pruning: bool,
+    use_move_ordering: bool,
+) -> Option<BestLine> {
+    let budget = SearchBudget {
+        movetime_ms: Some(movetime_ms),
+        ..SearchBudget::default()
+    };
+    get_best_move_limited(board, color, castling_rights, budget, use_pruning, use_move_ordering)
+}
+
+// Hint for the human player: a shallower search with random noise on top of each
+// root score, so lower levels suggest sensible but not necessarily best moves.
+// Level goes from 1 (loose) to 5 (engine-like).
+pub fn get_hint(
+    board: &[[i8; 8]; 8],
+    color: Color,
+    castling_rights: CastlingRights,
+    level: u8,
+) -> Option<(Square, Square)> {
+    let level = level.clamp(1, 5);
+    let depth = (level as i32 + 1) / 2;
+    let noise = (5 - level) as i32 * PAWN_VALUE;
+
+    let mut board_clone = *board;
+    let legal_moves = get_legal_moves(&board_clone, color, castling_rights);
+
+    let mut best: Option<(i32, (Square, Square))> = None;
 
     for move_ in legal_moves {
         let mut eval_count = 0;
         let (captured, new_rights) = make_move(&mut board_clone, move_, castling_rights);
-        let point = minimax(
+        let point = -negamax(
             &mut board_clone,
             get_opponent(color),
             depth - 1,
-            alpha,
-            beta,
+            -50000,
+            50000,
             new_rights,
-            use_pruning,
-            use_move_ordering,
+            true,
+            true,
             &mut eval_count,
         );
-        points_w_moves.push((point, move_, eval_count));
         undo_move(&mut board_clone, move_, captured);
+
+        let noisy = point + RNG.with(|rng| rng.borrow_mut().random_range(-noise..=noise));
+
+        if best.is_none_or(|(score, _)| noisy > score) {
+            best = Some((noisy, move_));
+        }
     }
 
-    if points_w_moves.is_empty() {
-        return None;
+    best.map(|(_, move_)| move_)
+}
+
+// Leaf count of the legal move tree, for checking move generation
+pub fn perft(board: &mut [[i8; 8]; 8], color: Color, castling_rights: CastlingRights, depth: u32) -> u64 {
+    if depth == 0 {
+        return 1;
+    }
+    let legal_moves = get_legal_moves(board, color, castling_rights);
+    if depth == 1 {
+        return legal_moves.len() as u64;
     }
 
-    let mut rng = rand::rng();
-    
-    let total_evals: u32 = points_w_moves.iter().map(|(_, _, c)| c).sum();
+    let mut nodes = 0;
+    for move_ in legal_moves {
+        let (captured, new_rights) = make_move(board, move_, castling_rights);
+        nodes += perft(board, get_opponent(color), new_rights, depth - 1);
+        undo_move(board, move_, captured);
+    }
+    nodes
+}
+
+// Legal destinations per origin square as bitmasks, indexed rank * 8 + file
+// for both the origin and the destination bit
+pub fn legal_move_masks(board: &[[i8; 8]; 8], color: Color, castling_rights: CastlingRights) -> [u64; 64] {
+    let mut masks = [0; 64];
+    for (from, to) in get_legal_moves(board, color, castling_rights) {
+        masks[from.index()] |= 1 << to.index();
+    }
+    masks
+}
+
+// What a move threatens, for "the engine threatens Qxf7#" style teaching hints
+pub struct MoveThreats {
+    // Opponent pieces that are attacked after the move but weren't before
+    pub newly_attacked: Vec<Square>,
+    // A mating move the mover would have if the opponent did nothing
+    pub mate_threat: Option<(Square, Square)>,
+}
+
+pub fn get_move_threats(
+    board: &[[i8; 8]; 8],
+    color: Color,
+    castling_rights: CastlingRights,
+    move_: (Square, Square),
+) -> MoveThreats {
+    let mut after = *board;
+    let (_, new_rights) = make_move(&mut after, move_, castling_rights);
+    let opponent = get_opponent(color);
+
+    let mut newly_attacked = Vec::new();
+    for square in Square::all() {
+        let piece = after[square];
+        let is_opponent_piece = piece != E && (piece > 0) == (opponent == Color::White);
+        if is_opponent_piece
+            && is_square_attacked(&after, square, color)
+            && !is_square_attacked(board, square, color)
+        {
+            newly_attacked.push(square);
+        }
+    }
+
+    // Give the mover a second move in a row and look for mate
+    let mut mate_threat = None;
+    if !is_in_check(&after, opponent) {
+        for threat in get_legal_moves(&after, color, new_rights) {
+            let (captured, rights_after_threat) = make_move(&mut after, threat, new_rights);
+            let is_mate = is_in_check(&after, opponent)
+                && get_legal_moves(&after, opponent, rights_after_threat).is_empty();
+            undo_move(&mut after, threat, captured);
+            if is_mate {
+                mate_threat = Some(threat);
+                break;
+            }
+        }
+    }
+
+    MoveThreats {
+        newly_attacked,
+        mate_threat,
+    }
+}
+
+// What kind of move was just played, for picking the UI sound and animation.
+// When several apply, the strongest wins: a capture that mates is Checkmate.
+// Promote is a pawn reaching the last rank; the engine leaves it a pawn for now.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum MoveClass {
+    Normal = 0,
+    Capture = 1,
+    Castle = 2,
+    Promote = 3,
+    Check = 4,
+    Checkmate = 5,
+    Illegal = 6,
+}
+
+pub fn classify_move(board: &[[i8; 8]; 8], color: Color, castling_rights: CastlingRights, move_: Move) -> MoveClass {
+    if !get_legal_moves(board, color, castling_rights).contains(&move_) {
+        return MoveClass::Illegal;
+    }
+
+    let (from, to) = move_;
+    let mut after = *board;
+    let (captured, new_rights) = make_move(&mut after, move_, castling_rights);
+    let opponent = get_opponent(color);
+    if is_in_check(&after, opponent) {
+        return if get_legal_moves(&after, opponent, new_rights).is_empty() {
+            MoveClass::Checkmate
+        } else {
+            MoveClass::Check
+        };
+    }
 
-    let best_score = if maximizing {
-        points_w_moves.iter().map(|(p, _, _)| *p).max().unwrap()
+    let piece = board[from].abs();
+    if piece == WP && (to.rank() == 0 || to.rank() == 7) {
+        MoveClass::Promote
+    } else if piece == WK && from.file().abs_diff(to.file()) == 2 {
+        MoveClass::Castle
+    } else if captured != E {
+        MoveClass::Capture
     } else {
-        points_w_moves.iter().map(|(p, _, _)| *p).min().unwrap()
+        MoveClass::Normal
+    }
+}
+
+// Best move for the side to move and the best reply to it, for the
+// "best move / best response" arrows on the analysis board
+pub fn get_plans(
+    board: &[[i8; 8]; 8],
+    color: Color,
+    depth: i32,
+    castling_rights: CastlingRights,
+) -> (Option<Move>, Option<Move>) {
+    let Some((from, to, _)) = get_best_move(board, color, depth, castling_rights, true, true, None) else {
+        return (None, None);
     };
 
-    let best_moves: Vec<_> = points_w_moves
-        .into_iter()
-        .filter(|(p, _, _)| *p == best_score)
-        .map(|(_, m, _)| m)
-        .collect();
+    let mut after = *board;
+    let (_, new_rights) = make_move(&mut after, (from, to), castling_rights);
+    let reply = get_best_move(&after, get_opponent(color), (depth - 1).max(1), new_rights, true, true, None)
+        .map(|(reply_from, reply_to, _)| (reply_from, reply_to));
 
-    let best_move = best_moves.choose(&mut rng).cloned();
-    
-    match best_move {
-        Some(m) => Some((m.0, m.1, total_evals)),
-        None => None,
+    (Some((from, to)), reply)
+}
+
+// Search score (centipawns, white-positive) after each ply of a game, for the
+// advantage graph.
+// Stops early if a move turns out to be illegal.
+pub fn eval_series(start: &FenPosition, moves: &[Move], depth: i32) -> Vec<i32> {
+    let mut positions = vec![start.clone()];
+    for &move_ in moves {
+        let position = positions.last().unwrap();
+        if !get_legal_moves(&position.board, position.color, position.castling_rights).contains(&move_) {
+            break;
+        }
+        let mut next = position.clone();
+        next.play(move_);
+        positions.push(next);
+    }
+    eval_positions(&positions, depth)
+}
+
+// Scores for positions[1..], each judged with the game so far: a position the
+// rules already call drawn (repetition of earlier positions in the list,
+// fifty moves by its own halfmove clock, stalemate, bare kings) scores 0
+// instead of being searched as if the game started there.
+pub fn eval_positions(positions: &[FenPosition], depth: i32) -> Vec<i32> {
+    let mut hashes = Vec::with_capacity(positions.len());
+    let mut series = Vec::with_capacity(positions.len().saturating_sub(1));
+
+    for (ply, position) in positions.iter().enumerate() {
+        // hashes holds the positions before this one
+        let termination = get_termination(
+            &position.board,
+            position.color,
+            position.castling_rights,
+            position.halfmove_clock,
+            &hashes,
+        );
+        if ply > 0 {
+            if termination.is_some_and(|termination| termination != Termination::Checkmate) {
+                series.push(0);
+            } else {
+                let mut board = position.board;
+                let mut eval_count = 0;
+                series.push(with_game_history(&hashes, position.halfmove_clock, || {
+                    minimax(
+                        &mut board,
+                        position.color,
+                        depth,
+                        -50000,
+                        50000,
+                        position.castling_rights,
+                        true,
+                        true,
+                        &mut eval_count,
+                    )
+                }));
+            }
+        }
+        hashes.push(zobrist_hash(&position.board, position.color, position.castling_rights));
+    }
+    series
+}
+
+// Middlegame position used to measure how fast this device searches
+const CALIBRATION_FEN: &str = "r1bq1rk1/pp2bppp/2n1pn2/3p4/2PP4/2N1PN2/PP3PPP/R2QKB1R w KQ - 0 8";
+const CALIBRATION_DEPTH: i32 = 3;
+// Rough growth in search time per extra ply with pruning and move ordering on
+const BRANCHING_FACTOR: f64 = 5.0;
+
+// Time a fixed search and pick the deepest search expected to finish within
+// target_ms on this device. Returns (recommended depth, elapsed ms, nodes per second).
+pub fn calibrate(target_ms: f64) -> (i32, f64, u32) {
+    let position = crate::chess::fen::parse_fen(CALIBRATION_FEN).unwrap();
+    let mut board = position.board;
+    let mut eval_count = 0;
+
+    let start = now_ms();
+    negamax(
+        &mut board,
+        position.color,
+        CALIBRATION_DEPTH,
+        -50000,
+        50000,
+        position.castling_rights,
+        true,
+        true,
+        &mut eval_count,
+    );
+    let elapsed = (now_ms() - start).max(1.0);
+
+    let mut depth = CALIBRATION_DEPTH;
+    let mut estimate = elapsed;
+    while estimate * BRANCHING_FACTOR <= target_ms && depth < 8 {
+        estimate *= BRANCHING_FACTOR;
+        depth += 1;
+    }
+    while estimate > target_ms && depth > 1 {
+        estimate /= BRANCHING_FACTOR;
+        depth -= 1;
+    }
+
+    let nps = (eval_count as f64 * 1000.0 / elapsed) as u32;
+    (depth, elapsed, nps)
+}
+
+// Fixed suite for comparing builds (native vs wasm, before vs after a change)
+pub(crate) const BENCH_FENS: [&str; 4] = [
+    "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+    "r1bq1rk1/pp2bppp/2n1pn2/3p4/2PP4/2N1PN2/PP3PPP/R2QKB1R w KQ - 0 8",
+    "r3k2r/ppp2ppp/2nqbn2/3pp3/3PP3/2NQBN2/PPP2PPP/R3K2R b KQkq - 0 1",
+    "8/5pk1/6p1/8/3R4/6P1/5PK1/r7 w - - 0 1",
+];
+
+// Search every bench position to `depth` with pruning and move ordering on.
+// Returns (leaf nodes, elapsed ms); the node count must match across builds.
+pub fn bench(depth: i32) -> (u64, f64) {
+    let mut nodes = 0;
+    let start = now_ms();
+    for fen in BENCH_FENS {
+        let position = crate::chess::fen::parse_fen(fen).unwrap();
+        let mut board = position.board;
+        let mut eval_count = 0;
+        negamax(
+            &mut board,
+            position.color,
+            depth,
+            -50000,
+            50000,
+            position.castling_rights,
+            true,
+            true,
+            &mut eval_count,
+        );
+        nodes += eval_count as u64;
+    }
+    (nodes, now_ms() - start)
+}
+
+// Legal move generation alone, on the bench positions: get_legal_moves is
+// where most of the search time goes. Returns (calls, elapsed ms).
+pub fn bench_legal_moves(iterations: u32) -> (u64, f64) {
+    let positions: Vec<_> = BENCH_FENS.iter().map(|fen| crate::chess::fen::parse_fen(fen).unwrap()).collect();
+    let mut calls = 0;
+    let start = now_ms();
+    for _ in 0..iterations {
+        for position in &positions {
+            std::hint::black_box(get_legal_moves(
+                std::hint::black_box(&position.board),
+                position.color,
+                position.castling_rights,
+            ));
+            calls += 1;
+        }
+    }
+    (calls, now_ms() - start)
+}
+
+// Share of non-pawn material left on the board: 1.0 with everything on, 0.0 in a
+// pure pawn ending
+pub fn material_phase(board: &[[i8; 8]; 8]) -> f64 {
+    // Two sets of N, B, R plus a queen
+    const FULL_MATERIAL: i32 = 2 * (3 + 3 + 3 + 3 + 5 + 5 + 9);
+    let mut material = 0;
+    for row in board {
+        for &piece in row {
+            if piece.abs() != WP && piece.abs() != WK {
+                material += get_piece_value(piece).abs();
+            }
+        }
+    }
+    (material.min(FULL_MATERIAL) as f64) / FULL_MATERIAL as f64
+}
+
+// Expected score for White (0.0 - 1.0) given a white-positive search score.
+// The same lead converts more reliably with less material left, so the curve is
+// steeper as pieces come off.
+pub fn win_probability(score: i32, board: &[[i8; 8]; 8]) -> f64 {
+    let pawns_per_decade = 2.5 + 1.5 * material_phase(board);
+    let pawns = score as f64 / PAWN_VALUE as f64;
+    1.0 / (1.0 + 10f64.powf(-pawns / pawns_per_decade))
+}
+
+// Why get_best_move_checked had to fall back to a plain legal move, or, for
+// InvalidPosition, why it returned none
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Fallback {
+    NoFallback = 0,
+    SearchPanicked = 1,
+    NoMoveReturned = 2,
+    IllegalMove = 3,
+    TimedOut = 4,
+    InvalidDepth = 5,
+    InvalidPosition = 6,
+}
+
+// Caps on a checked search, so one that runs away still hands back a move
+const CHECKED_MAX_NODES: u64 = 20_000_000;
+const CHECKED_MAX_MS: f64 = 30_000.0;
+
+// One king each, pawns off the back ranks, known piece codes, and the side
+// that just moved not left in check: anything else the search can't make sense of
+fn is_valid_position(board: &[[i8; 8]; 8], color: Color) -> bool {
+    let pieces = || board.iter().flatten();
+    pieces().all(|&piece| (BK..=WK).contains(&piece))
+        && pieces().filter(|&&piece| piece == WK).count() == 1
+        && pieces().filter(|&&piece| piece == BK).count() == 1
+        && [board[0], board[7]].iter().flatten().all(|&piece| piece.abs() != WP)
+        && !is_in_check(board, get_opponent(color))
+}
+
+// Put this thread's search state back after a search that unwound part way:
+// no limits or stop token, the game history as it was, an empty PV table
+fn reset_search_state(history_len: usize) {
+    set_limits(SearchLimits::default());
+    STOP_TOKEN.with(|token| *token.borrow_mut() = None);
+    HISTORY.with(|cell| cell.borrow_mut().truncate(history_len));
+    PV_TABLE.with(|table| table.borrow_mut().iter_mut().for_each(Vec::clear));
+    SELDEPTH.with(|cell| cell.set(0));
+}
+
+// get_best_move that always hands back a legal move while one exists. The
+// position and depth are checked before searching; the search itself runs
+// under a node and time cap, and if it stops at the cap, panics, comes back
+// empty, or returns a move that isn't legal here, the first legal move is
+// played instead and the reason is reported.
+// Panics are only caught natively; wasm builds abort on panic, so there the
+// input checks are what keep the search away from one.
+pub fn get_best_move_checked(
+    board: &[[i8; 8]; 8],
+    color: Color,
+    depth: i32,
+    castling_rights: CastlingRights,
+    use_pruning: bool,
+    use_move_ordering: bool,
+) -> (Option<(Square, Square, u32)>, Fallback) {
+    if !is_valid_position(board, color) {
+        return (None, Fallback::InvalidPosition);
+    }
+    let legal_moves = get_legal_moves(board, color, castling_rights);
+    let Some(&(first_from, first_to)) = legal_moves.first() else {
+        return (None, Fallback::NoFallback);
+    };
+    let fallback_move = Some((first_from, first_to, 0));
+    if !(1..=MAX_LIMITED_DEPTH).contains(&depth) {
+        return (fallback_move, Fallback::InvalidDepth);
+    }
+
+    let history_len = HISTORY.with(|cell| cell.borrow().len());
+    set_limits(SearchLimits {
+        deadline_ms: Some(now_ms() + CHECKED_MAX_MS),
+        max_nodes: Some(CHECKED_MAX_NODES),
+        ..SearchLimits::default()
+    });
+    let result = std::panic::catch_unwind(|| {
+        get_best_move(board, color, depth, castling_rights, use_pruning, use_move_ordering, None)
+    });
+    let stopped = search_stopped();
+    reset_search_state(history_len);
+
+    match result {
+        Err(_) => (fallback_move, Fallback::SearchPanicked),
+        Ok(_) if stopped => (fallback_move, Fallback::TimedOut),
+        Ok(None) => (fallback_move, Fallback::NoMoveReturned),
+        Ok(Some((from, to, _))) if !legal_moves.contains(&(from, to)) => {
+            (fallback_move, Fallback::IllegalMove)
+        }
+        Ok(best_move) => (best_move, Fallback::NoFallback),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chess::fen::parse_fen;
+
+    // Same squares and kings as a fresh scan, whatever order the lists are in
+    fn assert_in_step(board: &[[i8; 8]; 8], pieces: &PieceSquares) {
+        let fresh = PieceSquares::new(board);
+        for color in [Color::White, Color::Black] {
+            let mut tracked = pieces.of(color).to_vec();
+            let mut scanned = fresh.of(color).to_vec();
+            tracked.sort();
+            scanned.sort();
+            assert_eq!(tracked, scanned);
+            assert_eq!(pieces.king(color), fresh.king(color));
+        }
+    }
+
+    #[test]
+    fn piece_squares_follow_make_and_undo() {
+        // Castling both ways and captures for either side within two plies
+        let position = parse_fen("r3k2r/pp1n1ppp/2p5/3pP3/1b1P4/2N2N2/PP3PPP/R3K2R w KQkq - 0 1").unwrap();
+        let mut board = position.board;
+        let mut pieces = PieceSquares::new(&board);
+        for move_ in get_legal_moves(&board, position.color, position.castling_rights) {
+            let (captured, rights) = pieces.make_move(&mut board, move_, position.castling_rights);
+            assert_in_step(&board, &pieces);
+            for reply in get_legal_moves(&board, Color::Black, rights) {
+                let (reply_captured, _) = pieces.make_move(&mut board, reply, rights);
+                assert_in_step(&board, &pieces);
+                pieces.undo_move(&mut board, reply, reply_captured);
+                assert_in_step(&board, &pieces);
+            }
+            pieces.undo_move(&mut board, move_, captured);
+            assert_eq!(board, position.board);
+            assert_in_step(&board, &pieces);
+        }
     }
 }
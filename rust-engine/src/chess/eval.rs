@@ -0,0 +1,676 @@
+// Static evaluation used by the search: material plus positional terms, in
+// centipawns, from the point of view of the side to move. The terms below are
+// all written white-positive and flipped once at the end. evaluate_board in
+// engine.rs stays the plain material count in pawns for callers that only
+// want that.
+
+use crate::chess::pieces::{get_piece_value, Color, Square};
+use crate::chess::pieces::{BB, BK, BN, BP, BQ, BR, E, WB, WK, WN, WP, WQ, WR};
+use crate::chess::pst::piece_square;
+use crate::chess::simd::{piece_counts, PieceCounts};
+use crate::chess::tables::{KNIGHT_TARGETS, ORTHOGONAL, RAYS};
+use std::sync::atomic::{AtomicI32, Ordering};
+
+pub const PAWN_VALUE: i32 = 100;
+
+// Having the move is worth a little; also evens out odd/even depth scores
+const TEMPO: i32 = 10;
+
+// Game phase from the pieces left (pawns and kings don't count): a full set
+// weighs PHASE_TOTAL, scaled to 0 (bare) ..= PHASE_MAX (all pieces)
+const PHASE_WEIGHTS: [(i8, i32); 4] = [(WN, 1), (WB, 1), (WR, 2), (WQ, 4)];
+const PHASE_TOTAL: i32 = 24;
+pub const PHASE_MAX: i32 = 256;
+// At or below this it's an endgame: about a rook and a minor piece each
+const ENDGAME_PHASE: i32 = 64;
+// Minor pieces still at home for the game to count as in the opening
+const OPENING_UNDEVELOPED: usize = 4;
+const MINOR_HOMES: [(Square, i8); 8] = [
+    (Square::new(7, 1), WN), (Square::new(7, 6), WN), (Square::new(7, 2), WB), (Square::new(7, 5), WB),
+    (Square::new(0, 1), BN), (Square::new(0, 6), BN), (Square::new(0, 2), BB), (Square::new(0, 5), BB),
+];
+
+// A pawn is worth more in the endgame, where it can run
+const PAWN_ENDGAME_BONUS: i32 = 20;
+
+// Both bishops, with no pawns left on the board; with all sixteen it is worth
+// half as much, bishops needing open lines. Shared by every search thread,
+// set with set_bishop_pair_bonus.
+const DEFAULT_BISHOP_PAIR_BONUS: i32 = 60;
+static BISHOP_PAIR_BONUS: AtomicI32 = AtomicI32::new(DEFAULT_BISHOP_PAIR_BONUS);
+
+pub fn set_bishop_pair_bonus(centipawns: i32) {
+    BISHOP_PAIR_BONUS.store(centipawns, Ordering::Relaxed);
+}
+
+// Pawn structure penalties, middlegame and endgame: weak pawns are easier to
+// win the fewer pieces there are to defend them
+const DOUBLED_PAWN: Tapered = Tapered { mg: 10, eg: 20 };
+const ISOLATED_PAWN: Tapered = Tapered { mg: 10, eg: 15 };
+const BACKWARD_PAWN: Tapered = Tapered { mg: 8, eg: 10 };
+
+// Passed pawns by how far they have come, from the pawn's own second rank to
+// its seventh; worth far more in the endgame, with fewer pieces to stop them
+const PASSED_PAWN: [Tapered; 6] = [
+    Tapered { mg: 5, eg: 10 },
+    Tapered { mg: 10, eg: 20 },
+    Tapered { mg: 15, eg: 35 },
+    Tapered { mg: 25, eg: 60 },
+    Tapered { mg: 40, eg: 90 },
+    Tapered { mg: 60, eg: 130 },
+];
+
+// King safety, middlegame only: per file next to (and of) a king still on its
+// first two ranks, for a shield pawn that has stepped forward or is gone, and
+// for files without own pawns (semi-open) or without any pawns (open)
+const SHIELD_PAWN_ADVANCED: i32 = 10;
+const SHIELD_PAWN_MISSING: i32 = 25;
+const KING_SEMI_OPEN_FILE: i32 = 15;
+const KING_OPEN_FILE: i32 = 25;
+
+// King attacks, middlegame only: each hit on the enemy king or a square next
+// to it adds the piece's weight, and the total counts for more the more
+// pieces join in (percent by number of attackers, one alone is harmless)
+const ATTACK_WEIGHTS: [(i8, i32); 4] = [(WN, 8), (WB, 8), (WR, 12), (WQ, 20)];
+const ATTACKER_SCALE: [i32; 8] = [0, 0, 50, 75, 88, 94, 97, 99];
+
+// Rooks on files without own pawns (semi-open) or without any (open), and a
+// pair of rooks that see each other along a rank or file
+const ROOK_OPEN_FILE: Tapered = Tapered { mg: 25, eg: 10 };
+const ROOK_SEMI_OPEN_FILE: Tapered = Tapered { mg: 12, eg: 6 };
+const CONNECTED_ROOKS: Tapered = Tapered { mg: 10, eg: 5 };
+// On the seventh rank, if there are enemy pawns to eat there or the enemy
+// king is shut in on its back rank
+const ROOK_ON_SEVENTH: Tapered = Tapered { mg: 20, eg: 30 };
+
+// Knight outposts: defended by an own pawn and out of reach of enemy pawns,
+// by the knight's rank from its own side (fourth to sixth), plus a bit more on
+// the c-f files where it hits both wings
+const KNIGHT_OUTPOST: [Tapered; 3] = [
+    Tapered { mg: 10, eg: 5 },
+    Tapered { mg: 20, eg: 10 },
+    Tapered { mg: 25, eg: 12 },
+];
+const CENTRAL_OUTPOST: Tapered = Tapered { mg: 10, eg: 5 };
+
+// Trapped pieces
+const TRAPPED_BISHOP: i32 = 150;
+const TRAPPED_KNIGHT: i32 = 50;
+const BOXED_IN_ROOK: i32 = 40;
+
+// Space and the center
+const SPACE_DIVISOR: i32 = 8;
+const CENTER_PAWN: i32 = 20;
+const CENTER_PIECE: i32 = 10;
+const CENTER_PAWN_ATTACK: i32 = 8;
+const CENTER_PIECE_ATTACK: i32 = 4;
+const CENTER: [Square; 4] = [Square::new(3, 3), Square::new(3, 4), Square::new(4, 3), Square::new(4, 4)];
+
+// Drawish endings scale the whole score, out of SCALE_NORMAL
+const SCALE_NORMAL: i32 = 64;
+const SCALE_OCB_PURE: i32 = 32;
+const SCALE_OCB_WITH_ROOKS: i32 = 48;
+const SCALE_NO_PAWNS_SMALL_EDGE: i32 = 8;
+
+// Rook endgames
+const ROOK_BEHIND_PASSER: i32 = 25;
+// Per file between the defending king and the passed pawn it needs to stop
+const KING_CUT_OFF_PER_FILE: i32 = 15;
+
+pub fn evaluate(board: &[[i8; 8]; 8], side_to_move: Color) -> i32 {
+    let white_score = white_positive(board);
+    let relative = if side_to_move == Color::White { white_score } else { -white_score };
+    relative + TEMPO
+}
+
+// A term with separate middlegame and endgame values, blended by game phase
+#[derive(Clone, Copy, Default, Debug)]
+pub(crate) struct Tapered {
+    pub mg: i32,
+    pub eg: i32,
+}
+
+impl Tapered {
+    // The same value in every phase
+    fn flat(value: i32) -> Tapered {
+        Tapered { mg: value, eg: value }
+    }
+
+    fn blend(self, phase: i32) -> i32 {
+        (self.mg * phase + self.eg * (PHASE_MAX - phase)) / PHASE_MAX
+    }
+}
+
+impl std::ops::AddAssign for Tapered {
+    fn add_assign(&mut self, other: Tapered) {
+        self.mg += other.mg;
+        self.eg += other.eg;
+    }
+}
+
+impl std::ops::Mul<i32> for Tapered {
+    type Output = Tapered;
+
+    fn mul(self, factor: i32) -> Tapered {
+        Tapered { mg: self.mg * factor, eg: self.eg * factor }
+    }
+}
+
+fn white_positive(board: &[[i8; 8]; 8]) -> i32 {
+    let counts = piece_counts(board);
+    let mut score = Tapered::flat(material(&counts) * PAWN_VALUE);
+    let pawns = counts[(WP + 6) as usize] as i32 - counts[(BP + 6) as usize] as i32;
+    score += Tapered { mg: 0, eg: pawns * PAWN_ENDGAME_BONUS };
+    score += Tapered::flat(imbalance(&counts));
+    score += piece_square(board);
+    score += pawn_structure(board);
+    score += passed_pawns(board);
+    score += rooks(board);
+    score += knight_outposts(board);
+    score += Tapered { mg: king_safety(board) + king_attack(board), eg: 0 };
+    score += Tapered::flat(trapped_pieces(board));
+    score += Tapered::flat(space(board, &counts) + center_control(board));
+    if is_rook_endgame(&counts) {
+        score += Tapered::flat(rook_endgame(board));
+    }
+    let score = score.blend(phase(&counts));
+    score * drawish_scale(board, score) / SCALE_NORMAL
+}
+
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+pub enum GamePhase {
+    Opening = 0,
+    Middlegame = 1,
+    Endgame = 2,
+}
+
+// PHASE_MAX with every piece on the board, down to 0 with none (promotions
+// can't push it past PHASE_MAX). The evaluation tapers by it.
+pub fn get_game_phase(board: &[[i8; 8]; 8]) -> i32 {
+    phase(&piece_counts(board))
+}
+
+// Named phase for display: the opening while most minor pieces are undeveloped
+// and nothing has been traded, the endgame from ENDGAME_PHASE down
+pub fn classify_game_phase(board: &[[i8; 8]; 8]) -> GamePhase {
+    let phase = get_game_phase(board);
+    let undeveloped = MINOR_HOMES.iter().filter(|&&(square, piece)| board[square] == piece).count();
+    if phase <= ENDGAME_PHASE {
+        GamePhase::Endgame
+    } else if phase == PHASE_MAX && undeveloped >= OPENING_UNDEVELOPED {
+        GamePhase::Opening
+    } else {
+        GamePhase::Middlegame
+    }
+}
+
+fn phase(counts: &PieceCounts) -> i32 {
+    let count = |piece: i8| (counts[(piece + 6) as usize] + counts[(-piece + 6) as usize]) as i32;
+    let weight: i32 = PHASE_WEIGHTS.iter().map(|&(piece, weight)| count(piece) * weight).sum();
+    weight.min(PHASE_TOTAL) * PHASE_MAX / PHASE_TOTAL
+}
+
+// Material combinations worth more (or less) than their parts
+fn imbalance(counts: &PieceCounts) -> i32 {
+    let count = |piece: i8| counts[(piece + 6) as usize] as i32;
+    let pawns = count(WP) + count(BP);
+    let pair_bonus = BISHOP_PAIR_BONUS.load(Ordering::Relaxed) * (32 - pawns) / 32;
+    let pair = |bishops: i32| if bishops >= 2 { pair_bonus } else { 0 };
+    pair(count(WB)) - pair(count(BB))
+}
+
+// Same as evaluate_board, from the piece counts
+fn material(counts: &PieceCounts) -> i32 {
+    counts.iter().enumerate().map(|(i, &count)| get_piece_value(i as i8 - 6) * count as i32).sum()
+}
+
+// How much of the score is real in endings that are hard or impossible to
+// win whatever the material count says: opposite-colored bishops, and a
+// pawnless side that is only up a minor piece or less (R+B vs R, B vs -).
+fn drawish_scale(board: &[[i8; 8]; 8], score: i32) -> i32 {
+    let mut white_bishops = Vec::new();
+    let mut black_bishops = Vec::new();
+    let (mut white_pieces, mut black_pieces) = (0, 0);
+    let (mut white_pawns, mut black_pawns) = (0, 0);
+    let mut rooks_only = true;
+
+    for (r, row) in board.iter().enumerate() {
+        for (f, &piece) in row.iter().enumerate() {
+            match piece {
+                WP => white_pawns += 1,
+                BP => black_pawns += 1,
+                WK | BK | E => {}
+                _ => {
+                    let value = get_piece_value(piece);
+                    if value > 0 { white_pieces += value } else { black_pieces -= value }
+                    match piece {
+                        WB => white_bishops.push((r + f) % 2),
+                        BB => black_bishops.push((r + f) % 2),
+                        WR | BR => {}
+                        _ => rooks_only = false,
+                    }
+                }
+            }
+        }
+    }
+
+    let (strong_pawns, edge) = if score >= 0 {
+        (white_pawns, white_pieces - black_pieces)
+    } else {
+        (black_pawns, black_pieces - white_pieces)
+    };
+    if strong_pawns == 0 && edge <= 3 {
+        return SCALE_NO_PAWNS_SMALL_EDGE;
+    }
+
+    let opposite_bishops = white_bishops.len() == 1
+        && black_bishops.len() == 1
+        && white_bishops[0] != black_bishops[0];
+    if opposite_bishops {
+        // Bishops are the only pieces besides kings and pawns, or rooks come along
+        if white_pieces == 3 && black_pieces == 3 {
+            return SCALE_OCB_PURE;
+        }
+        if rooks_only && white_pieces == black_pieces {
+            return SCALE_OCB_WITH_ROOKS;
+        }
+    }
+    SCALE_NORMAL
+}
+
+// No pawn of the other side in front of it on its own or an adjacent file
+pub fn is_passed_pawn(board: &[[i8; 8]; 8], rank: usize, file: usize) -> bool {
+    let (enemy_pawn, ahead) = match board[rank][file] {
+        WP => (BP, 0..rank),
+        BP => (WP, rank + 1..8),
+        _ => return false,
+    };
+    let files = file.saturating_sub(1)..=(file + 1).min(7);
+    !ahead
+        .into_iter()
+        .any(|r| files.clone().any(|f| board[r][f] == enemy_pawn))
+}
+
+// Doubled pawns (each one past the first on a file), isolated pawns (no own
+// pawn on either neighboring file) and backward pawns (every neighbor already
+// past it, so none can come up to defend it, and an enemy pawn guards the
+// square in front of it)
+fn pawn_structure(board: &[[i8; 8]; 8]) -> Tapered {
+    let mut score = Tapered::default();
+    for (sign, pawn, enemy_pawn) in [(1, WP, BP), (-1, BP, WP)] {
+        // Per file: how many of this side's pawns, and how far back the
+        // rearmost one is, counted in rows from this side's far end
+        let back = |r: usize| if sign == 1 { r } else { 7 - r };
+        let mut counts = [0; 8];
+        let mut rearmost: [Option<usize>; 8] = [None; 8];
+        for (r, row) in board.iter().enumerate() {
+            for (f, &piece) in row.iter().enumerate() {
+                if piece == pawn {
+                    counts[f] += 1;
+                    rearmost[f] = rearmost[f].max(Some(back(r)));
+                }
+            }
+        }
+
+        let mut penalty = Tapered::default();
+        for &count in &counts {
+            if count > 1 {
+                penalty += DOUBLED_PAWN * (count - 1);
+            }
+        }
+        for (r, row) in board.iter().enumerate() {
+            for (f, &piece) in row.iter().enumerate() {
+                if piece != pawn {
+                    continue;
+                }
+                let neighbors = [f.wrapping_sub(1), f + 1].map(|nf| rearmost.get(nf).copied().flatten());
+                if neighbors.iter().all(Option::is_none) {
+                    penalty += ISOLATED_PAWN;
+                    continue;
+                }
+                let can_be_supported = neighbors.iter().flatten().any(|&nb| nb >= back(r));
+                let stop = if sign == 1 { r.wrapping_sub(1) } else { r + 1 };
+                if !can_be_supported && stop < 8 && pawn_attacks(board, Square::new(stop, f), enemy_pawn) {
+                    penalty += BACKWARD_PAWN;
+                }
+            }
+        }
+        score += penalty * -sign;
+    }
+    score
+}
+
+fn passed_pawns(board: &[[i8; 8]; 8]) -> Tapered {
+    let mut score = Tapered::default();
+    for (r, row) in board.iter().enumerate() {
+        for (f, &piece) in row.iter().enumerate() {
+            if !is_passed_pawn(board, r, f) {
+                continue;
+            }
+            // Rows advanced past the second rank; a pawn left on the last
+            // rank (the engine doesn't promote) gets nothing
+            let (sign, advanced) = if piece == WP { (1, 6usize.checked_sub(r)) } else { (-1, r.checked_sub(1)) };
+            if let Some(bonus) = advanced.and_then(|advanced| PASSED_PAWN.get(advanced)) {
+                score += *bonus * sign;
+            }
+        }
+    }
+    score
+}
+
+fn king_safety(board: &[[i8; 8]; 8]) -> i32 {
+    let mut score = 0;
+    for (sign, king, own_pawn, enemy_pawn) in [(1, WK, WP, BP), (-1, BK, BP, WP)] {
+        let Some(king_square) = find(board, king) else { continue };
+        let (kr, kf) = (king_square.rank(), king_square.file());
+        // Rows counted from this side's back rank
+        let row = |relative: usize| if sign == 1 { 7 - relative } else { relative };
+        let king_row = if sign == 1 { 7 - kr } else { kr };
+        if king_row > 1 {
+            continue;
+        }
+
+        let mut penalty = 0;
+        for f in kf.saturating_sub(1)..=(kf + 1).min(7) {
+            let file = board.map(|rank| rank[f]);
+            if file[row(king_row + 1)] == own_pawn {
+                // Shield intact
+            } else if file[row(king_row + 2)] == own_pawn {
+                penalty += SHIELD_PAWN_ADVANCED;
+            } else {
+                penalty += SHIELD_PAWN_MISSING;
+            }
+
+            if !file.contains(&own_pawn) {
+                penalty += if file.contains(&enemy_pawn) { KING_SEMI_OPEN_FILE } else { KING_OPEN_FILE };
+            }
+        }
+        score -= sign * penalty;
+    }
+    score
+}
+
+fn king_attack(board: &[[i8; 8]; 8]) -> i32 {
+    let mut score = 0;
+    for (sign, enemy_king) in [(1, BK), (-1, WK)] {
+        let Some(king_square) = find(board, enemy_king) else { continue };
+        let (kr, kf) = (king_square.rank(), king_square.file());
+        let own = |piece: i8| piece != E && (piece > 0) == (sign == 1);
+        let weight_of = |piece: i8| ATTACK_WEIGHTS.iter().find(|&&(kind, _)| kind == piece.abs()).map(|&(_, weight)| weight);
+
+        // Looked at from each zone square: the knights a jump away and the
+        // first piece down each ray, if it slides that way
+        let mut attackers = 0u64;
+        let mut weight = 0;
+        let mut hit = |square: Square| {
+            if let Some(piece_weight) = weight_of(board[square]) {
+                attackers |= 1 << square.index();
+                weight += piece_weight;
+            }
+        };
+        let zone_files = kf.saturating_sub(1)..=(kf + 1).min(7);
+        let zone = (kr.saturating_sub(1)..=(kr + 1).min(7)).flat_map(|r| zone_files.clone().map(move |f| (r, f)));
+        for (zr, zf) in zone {
+            for &square in KNIGHT_TARGETS[zr][zf].as_slice() {
+                if own(board[square]) && board[square].abs() == WN {
+                    hit(square);
+                }
+            }
+            for (direction, ray) in RAYS[zr][zf].iter().enumerate() {
+                let Some(&square) = ray.as_slice().iter().find(|&&square| board[square] != E) else { continue };
+                let piece = board[square];
+                let slides = if ORTHOGONAL.contains(&direction) { [WR, WQ] } else { [WB, WQ] };
+                if own(piece) && slides.contains(&piece.abs()) {
+                    hit(square);
+                }
+            }
+        }
+        let attackers = attackers.count_ones() as usize;
+        score += sign * weight * ATTACKER_SCALE[attackers.min(ATTACKER_SCALE.len() - 1)] / 100;
+    }
+    score
+}
+
+fn rooks(board: &[[i8; 8]; 8]) -> Tapered {
+    let mut score = Tapered::default();
+    for (sign, rook, own_pawn, enemy_pawn, enemy_king) in [(1, WR, WP, BP, BK), (-1, BR, BP, WP, WK)] {
+        // The enemy's second and first ranks
+        let (seventh, eighth) = if sign == 1 { (1, 0) } else { (6, 7) };
+        let seventh_matters = board[seventh].contains(&enemy_pawn) || board[eighth].contains(&enemy_king);
+
+        let mut squares = Vec::with_capacity(2);
+        for (r, f) in (0..8).flat_map(|r| (0..8).map(move |f| (r, f))) {
+            if board[r][f] != rook {
+                continue;
+            }
+            squares.push((r, f));
+            if r == seventh && seventh_matters {
+                score += ROOK_ON_SEVENTH * sign;
+            }
+            let file = board.map(|rank| rank[f]);
+            if !file.contains(&own_pawn) {
+                score += if file.contains(&enemy_pawn) { ROOK_SEMI_OPEN_FILE } else { ROOK_OPEN_FILE } * sign;
+            }
+        }
+
+        if let [(r1, f1), (r2, f2)] = squares[..] {
+            let connected = if r1 == r2 {
+                (f1.min(f2) + 1..f1.max(f2)).all(|f| board[r1][f] == E)
+            } else if f1 == f2 {
+                (r1.min(r2) + 1..r1.max(r2)).all(|r| board[r][f1] == E)
+            } else {
+                false
+            };
+            if connected {
+                score += CONNECTED_ROOKS * sign;
+            }
+        }
+    }
+    score
+}
+
+fn knight_outposts(board: &[[i8; 8]; 8]) -> Tapered {
+    let mut score = Tapered::default();
+    for (r, f) in (0..8).flat_map(|r| (0..8).map(move |f| (r, f))) {
+        let (sign, own_pawn, enemy_pawn, rank, ahead) = match board[r][f] {
+            WN => (1, WP, BP, 7 - r, 0..r),
+            BN => (-1, BP, WP, r, r + 1..8),
+            _ => continue,
+        };
+        // Ranks counted from 0, so the fourth rank is 3
+        let Some(&bonus) = rank.checked_sub(3).and_then(|i| KNIGHT_OUTPOST.get(i)) else { continue };
+        let challengers = [f.wrapping_sub(1), f + 1]
+            .iter()
+            .filter(|&&nf| nf < 8)
+            .any(|&nf| ahead.clone().any(|ar| board[ar][nf] == enemy_pawn));
+        if challengers || !pawn_attacks(board, Square::new(r, f), own_pawn) {
+            continue;
+        }
+        score += bonus * sign;
+        if (2..=5).contains(&f) {
+            score += CENTRAL_OUTPOST * sign;
+        }
+    }
+    score
+}
+
+// Penalties for the classic traps material counting never sees: a bishop on
+// a7/h7 (a2/h2 for Black) shut in by a pawn on b6/g6, a knight on the rim
+// whose every square is taken by its own pieces or covered by enemy pawns,
+// and a rook stuck in the corner behind its own uncastled king.
+fn trapped_pieces(board: &[[i8; 8]; 8]) -> i32 {
+    let mut score = 0;
+    for (sign, bishop, knight, rook, king, enemy_pawn) in
+        [(1, WB, WN, WR, WK, BP), (-1, BB, BN, BR, BK, WP)]
+    {
+        // Rank counted from this side's back rank, so one rule covers both colors
+        let row = |relative: usize| if sign == 1 { 7 - relative } else { relative };
+        let own = |piece: i8| piece != E && (piece > 0) == (sign == 1);
+
+        for (bishop_file, pawn_file) in [(0, 1), (7, 6)] {
+            if board[row(6)][bishop_file] == bishop && board[row(5)][pawn_file] == enemy_pawn {
+                score -= sign * TRAPPED_BISHOP;
+            }
+        }
+
+        for (r, f) in (0..8).flat_map(|r| (0..8).map(move |f| (r, f))) {
+            if board[r][f] != knight || !(r == 0 || r == 7 || f == 0 || f == 7) {
+                continue;
+            }
+            let has_safe_square = KNIGHT_TARGETS[r][f].as_slice().iter().any(|&target| {
+                // Enemy pawns capture towards our back rank
+                let covered = [-1, 1]
+                    .into_iter()
+                    .filter_map(|df| target.offset(-sign as isize, df))
+                    .any(|square| board[square] == enemy_pawn);
+                !own(board[target]) && !covered
+            });
+            if !has_safe_square {
+                score -= sign * TRAPPED_KNIGHT;
+            }
+        }
+
+        // King stepped towards the corner without castling and the rook can't
+        // get out over its own pawn
+        let home = row(0);
+        if let Some(king_file) = (0..8).find(|&f| board[home][f] == king) {
+            for (f, &piece) in board[home].iter().enumerate() {
+                let cornered = (king_file >= 5 && f > king_file) || (king_file <= 3 && f < king_file);
+                if cornered && piece == rook && own(board[row(1)][f]) {
+                    score -= sign * BOXED_IN_ROOK;
+                }
+            }
+        }
+    }
+    score
+}
+
+// Room to maneuver behind the pawns: safe squares on files c-f of a side's
+// ranks 2-4 (counted twice when an own pawn stands in front of them), worth
+// more the more pieces there are that need the room
+fn space(board: &[[i8; 8]; 8], counts: &PieceCounts) -> i32 {
+    let mut score = 0;
+    for (sign, own_pawn, enemy_pawn) in [(1, WP, BP), (-1, BP, WP)] {
+        let row = |relative: usize| if sign == 1 { 7 - relative } else { relative };
+        let pieces: i32 = [WN, WB, WR, WQ].iter().map(|&piece| counts[(piece as i32 * sign + 6) as usize] as i32).sum();
+
+        let mut squares = 0;
+        for relative in 1..=3 {
+            let r = row(relative);
+            for f in 2..=5 {
+                if board[r][f] == own_pawn || pawn_attacks(board, Square::new(r, f), enemy_pawn) {
+                    continue;
+                }
+                squares += 1;
+                if (1..=3).any(|ahead| relative + ahead < 8 && board[row(relative + ahead)][f] == own_pawn) {
+                    squares += 1;
+                }
+            }
+        }
+        score += sign * squares * pieces / SPACE_DIVISOR;
+    }
+    score
+}
+
+// Occupying and hitting d4, e4, d5 and e5
+fn center_control(board: &[[i8; 8]; 8]) -> i32 {
+    let mut score = 0;
+    for &square in &CENTER {
+        match board[square] {
+            WP => score += CENTER_PAWN,
+            BP => score -= CENTER_PAWN,
+            WN | WB => score += CENTER_PIECE,
+            BN | BB => score -= CENTER_PIECE,
+            _ => {}
+        }
+        if pawn_attacks(board, square, WP) {
+            score += CENTER_PAWN_ATTACK;
+        }
+        if pawn_attacks(board, square, BP) {
+            score -= CENTER_PAWN_ATTACK;
+        }
+    }
+
+    // Pieces hitting each center square, found from the square outward like
+    // king_attack: the knights a jump away and the first piece down each ray,
+    // if it slides that way. A square held by the piece's own side isn't a
+    // move for it, so it doesn't count.
+    for &center in &CENTER {
+        let occupant = board[center];
+        let mut hit = |piece: i8| {
+            if occupant == E || (occupant > 0) != (piece > 0) {
+                score += piece.signum() as i32 * CENTER_PIECE_ATTACK;
+            }
+        };
+        for &square in KNIGHT_TARGETS[center.rank()][center.file()].as_slice() {
+            if board[square].abs() == WN {
+                hit(board[square]);
+            }
+        }
+        for (direction, ray) in RAYS[center.rank()][center.file()].iter().enumerate() {
+            let Some(&square) = ray.as_slice().iter().find(|&&square| board[square] != E) else { continue };
+            let slides = if ORTHOGONAL.contains(&direction) { [WR, WQ] } else { [WB, WQ] };
+            if slides.contains(&board[square].abs()) {
+                hit(board[square]);
+            }
+        }
+    }
+    score
+}
+
+// Is `square` covered by a pawn of the given code (WP or BP)?
+fn pawn_attacks(board: &[[i8; 8]; 8], square: Square, pawn: i8) -> bool {
+    // White pawns capture upwards, so they sit one row below the square
+    let pawn_row = if pawn == WP { 1 } else { -1 };
+    [-1, 1].into_iter().filter_map(|df| square.offset(pawn_row, df)).any(|from| board[from] == pawn)
+}
+
+// Rooks (and pawns) only, with at least one rook on the board
+fn is_rook_endgame(counts: &PieceCounts) -> bool {
+    let count = |piece: i8| counts[(piece + 6) as usize];
+    let minors_and_queens = [WQ, BQ, WB, BB, WN, BN].iter().map(|&piece| count(piece)).sum::<u8>();
+    minors_and_queens == 0 && count(WR) + count(BR) > 0
+}
+
+fn rook_endgame(board: &[[i8; 8]; 8]) -> i32 {
+    let mut score = 0;
+    let passers: Vec<(usize, usize)> = (0..8)
+        .flat_map(|r| (0..8).map(move |f| (r, f)))
+        .filter(|&(r, f)| is_passed_pawn(board, r, f))
+        .collect();
+
+    for (r, f) in (0..8).flat_map(|r| (0..8).map(move |f| (r, f))) {
+        let rook = board[r][f];
+        if rook != WR && rook != BR {
+            continue;
+        }
+        let sign = if rook == WR { 1 } else { -1 };
+        let (own_pawn, enemy_king) = if rook == WR { (WP, BK) } else { (BP, WK) };
+
+        for &(pr, pf) in &passers {
+            // Behind any passer, ours to push or theirs to stop: on its file,
+            // on the side it is moving away from, with nothing in between
+            let pawn = board[pr][pf];
+            let behind = if pawn == WP { r > pr } else { r < pr };
+            let mut between = if r < pr { r + 1..pr } else { pr + 1..r };
+            if pf == f && behind && between.all(|i| board[i][f] == E) {
+                score += sign * ROOK_BEHIND_PASSER;
+            }
+
+            // Our rook on a file between the enemy king and our passer keeps
+            // the king from reaching it
+            if pawn == own_pawn {
+                if let Some(kf) = find(board, enemy_king).map(Square::file) {
+                    if (kf < f && f < pf) || (pf < f && f < kf) {
+                        score += sign * KING_CUT_OFF_PER_FILE * kf.abs_diff(f) as i32;
+                    }
+                }
+            }
+        }
+    }
+    score
+}
+
+fn find(board: &[[i8; 8]; 8], piece: i8) -> Option<Square> {
+    Square::all().find(|&square| board[square] == piece)
+}
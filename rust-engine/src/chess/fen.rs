@@ -0,0 +1,210 @@
+use crate::chess::engine::{get_opponent, make_move, CastlingRights, CASTLE_BK, CASTLE_BQ, CASTLE_WK, CASTLE_WQ};
+use crate::chess::pieces::{Color, Move, Piece, Square, BK, BP, BR, E, WK, WP, WR};
+
+pub const START_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+#[derive(Clone)]
+pub struct FenPosition {
+    pub board: [[i8; 8]; 8],
+    pub color: Color,
+    pub castling_rights: CastlingRights,
+    pub en_passant: Option<Square>,
+    pub halfmove_clock: u32,
+    pub fullmove_number: u32,
+}
+
+impl FenPosition {
+    // Play a move (assumed legal) and update the side to move, rights and counters
+    pub fn play(&mut self, move_: Move) {
+        let (from, _) = move_;
+        let is_pawn_move = self.board[from].abs() == WP;
+        let (captured, new_rights) = make_move(&mut self.board, move_, self.castling_rights);
+
+        self.castling_rights = new_rights;
+        self.en_passant = None;
+        self.halfmove_clock = if is_pawn_move || captured != E {
+            0
+        } else {
+            self.halfmove_clock + 1
+        };
+        if self.color == Color::Black {
+            self.fullmove_number += 1;
+        }
+        self.color = get_opponent(self.color);
+    }
+
+    // Pass: hand the move to the other side without moving anything
+    pub fn play_null(&mut self) {
+        self.en_passant = None;
+        self.halfmove_clock += 1;
+        if self.color == Color::Black {
+            self.fullmove_number += 1;
+        }
+        self.color = get_opponent(self.color);
+    }
+}
+
+// "e4" -> rank 4, file 4; row 0 is rank 8
+pub fn parse_square(name: &str) -> Option<Square> {
+    let mut chars = name.chars();
+    let file = chars.next()?;
+    let rank = chars.next()?;
+    if chars.next().is_some() || !('a'..='h').contains(&file) || !('1'..='8').contains(&rank) {
+        return None;
+    }
+    Some(Square::new(8 - rank.to_digit(10)? as usize, file as usize - 'a' as usize))
+}
+
+pub fn square_name(square: Square) -> String {
+    format!("{}{}", (b'a' + square.file() as u8) as char, 8 - square.rank())
+}
+
+pub fn parse_fen(fen: &str) -> Result<FenPosition, String> {
+    let fields: Vec<&str> = fen.split_whitespace().collect();
+    if fields.len() < 4 {
+        return Err(format!("FEN needs at least 4 fields, got {}", fields.len()));
+    }
+
+    let rows: Vec<&str> = fields[0].split('/').collect();
+    if rows.len() != 8 {
+        return Err(format!("FEN board needs 8 ranks, got {}", rows.len()));
+    }
+    let mut board = [[E; 8]; 8];
+    for (rank, row) in rows.iter().enumerate() {
+        let mut file = 0;
+        for c in row.chars() {
+            if let Some(skip) = c.to_digit(10) {
+                file += skip as usize;
+            } else {
+                let piece = Piece::from_char(c).ok_or(format!("Unknown piece '{}' in FEN", c))?;
+                if file >= 8 {
+                    return Err(format!("Rank {} of the FEN is too long", 8 - rank));
+                }
+                board[rank][file] = i8::from(piece);
+                file += 1;
+            }
+        }
+        if file != 8 {
+            return Err(format!("Rank {} of the FEN does not have 8 files", 8 - rank));
+        }
+    }
+
+    let color = match fields[1] {
+        "w" => Color::White,
+        "b" => Color::Black,
+        other => return Err(format!("Unknown side to move '{}'", other)),
+    };
+
+    let castling_rights = CastlingRights::from_fen(fields[2])?;
+
+    let en_passant = if fields[3] == "-" {
+        None
+    } else {
+        Some(parse_square(fields[3]).ok_or(format!("Bad en passant square '{}'", fields[3]))?)
+    };
+
+    let halfmove_clock = match fields.get(4) {
+        Some(field) => field.parse().map_err(|_| format!("Bad halfmove clock '{}'", field))?,
+        None => 0,
+    };
+    let fullmove_number = match fields.get(5) {
+        Some(field) => field.parse().map_err(|_| format!("Bad fullmove number '{}'", field))?,
+        None => 1,
+    };
+
+    Ok(FenPosition {
+        board,
+        color,
+        castling_rights,
+        en_passant,
+        halfmove_clock,
+        fullmove_number,
+    })
+}
+
+pub fn to_fen(position: &FenPosition) -> String {
+    let mut placement = String::new();
+    for (rank, row) in position.board.iter().enumerate() {
+        let mut empty = 0;
+        for &piece in row {
+            if piece == E {
+                empty += 1;
+                continue;
+            }
+            if empty > 0 {
+                placement.push_str(&empty.to_string());
+                empty = 0;
+            }
+            placement.push(Piece::try_from(piece).map_or('?', Piece::to_char));
+        }
+        if empty > 0 {
+            placement.push_str(&empty.to_string());
+        }
+        if rank < 7 {
+            placement.push('/');
+        }
+    }
+
+    let color = if position.color == Color::White { "w" } else { "b" };
+
+    let en_passant = position.en_passant.map_or("-".to_string(), square_name);
+
+    format!(
+        "{} {} {} {} {} {}",
+        placement, color, position.castling_rights, en_passant, position.halfmove_clock, position.fullmove_number
+    )
+}
+
+// FEN with the move counters dropped, castling flags that can no longer be
+// used removed, and the en passant square kept only when a capture is possible,
+// so transpositions of the same position always produce the same string
+pub fn canonical_fen(fen: &str) -> Result<String, String> {
+    let mut position = parse_fen(fen)?;
+    let board = &position.board;
+
+    for (mask, rank, rook_file, king) in [
+        (CASTLE_WK, 7, 7, WK),
+        (CASTLE_WQ, 7, 0, WK),
+        (CASTLE_BK, 0, 7, BK),
+        (CASTLE_BQ, 0, 0, BK),
+    ] {
+        let rook = if king == WK { WR } else { BR };
+        if board[rank][4] != king || board[rank][rook_file] != rook {
+            position.castling_rights = position.castling_rights.without(mask);
+        }
+    }
+
+    if let Some(ep_square) = position.en_passant {
+        let (ep_rank, ep_file) = (ep_square.rank(), ep_square.file());
+        let (pawn, pawn_rank) = match position.color {
+            Color::White => (WP, 3),
+            Color::Black => (BP, 4),
+        };
+        let capturable = [ep_file.wrapping_sub(1), ep_file + 1]
+            .iter()
+            .any(|&f| f < 8 && board[pawn_rank][f] == pawn);
+        let expected_rank = if position.color == Color::White { 2 } else { 5 };
+        if !capturable || ep_rank != expected_rank {
+            position.en_passant = None;
+        }
+    }
+
+    let full = to_fen(&position);
+    let fields: Vec<&str> = full.split_whitespace().take(4).collect();
+    Ok(fields.join(" "))
+}
+
+// Stable 64-bit key for a position (FNV-1a of the canonical FEN), identical
+// natively and in wasm, for use as a cache or localStorage key
+pub fn position_key(fen: &str) -> Result<u64, String> {
+    Ok(fnv1a(canonical_fen(fen)?.as_bytes()))
+}
+
+pub(crate) fn fnv1a(bytes: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for &byte in bytes {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+    hash
+}
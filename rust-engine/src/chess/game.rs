@@ -0,0 +1,352 @@
+use crate::chess::engine::{get_legal_moves, is_in_check};
+use crate::chess::fen::{parse_fen, to_fen, FenPosition, START_FEN};
+use crate::chess::notation::{move_to_san, parse_san, PieceLetters};
+use crate::chess::pgn::PgnGame;
+use crate::chess::pieces::{Color, Move};
+use crate::chess::status::{get_termination, Termination};
+use crate::chess::tables::zobrist_hash;
+
+pub type NodeId = usize;
+
+const ROOT: NodeId = 0;
+
+#[derive(Clone)]
+struct Node {
+    // Move leading here from the parent; None for the root and for null moves
+    move_: Option<Move>,
+    parent: Option<NodeId>,
+    // The first child continues the main line, the rest are variations
+    children: Vec<NodeId>,
+    position: FenPosition,
+}
+
+// A game as a tree of moves from a starting position, with the cursor on the
+// position currently shown. Nodes live in an arena and are addressed by
+// NodeId; deleted nodes are only unlinked, so ids handed out stay valid.
+#[derive(Clone)]
+pub struct Game {
+    nodes: Vec<Node>,
+    cursor: NodeId,
+}
+
+impl Default for Game {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Game {
+    pub fn new() -> Self {
+        Self::from_fen(START_FEN).unwrap()
+    }
+
+    pub fn from_fen(fen: &str) -> Result<Self, String> {
+        let start = parse_fen(fen)?;
+        Ok(Game {
+            nodes: vec![Node {
+                move_: None,
+                parent: None,
+                children: Vec::new(),
+                position: start,
+            }],
+            cursor: ROOT,
+        })
+    }
+
+    pub fn position(&self) -> &FenPosition {
+        &self.nodes[self.cursor].position
+    }
+
+    pub fn current_node(&self) -> NodeId {
+        self.cursor
+    }
+
+    // Half-moves from the start to the current node, null moves included
+    pub fn ply(&self) -> usize {
+        self.path(self.cursor).len() - 1
+    }
+
+    // Moves from the start to the current node (null moves are skipped)
+    pub fn moves(&self) -> Vec<Move> {
+        self.path(self.cursor)
+            .iter()
+            .filter_map(|&id| self.nodes[id].move_)
+            .collect()
+    }
+
+    // How the game ended at the current node, if it did, with the line that
+    // led here as the repetition history
+    pub fn termination(&self) -> Option<Termination> {
+        let position = self.position();
+        get_termination(&position.board, position.color, position.castling_rights, position.halfmove_clock, &self.history_hashes())
+    }
+
+    // Zobrist hashes of the positions before the current node, oldest first,
+    // for searching from here with engine::with_game_history
+    pub fn history_hashes(&self) -> Vec<u64> {
+        let path = self.path(self.cursor);
+        path[..path.len() - 1]
+            .iter()
+            .map(|&id| {
+                let position = &self.nodes[id].position;
+                zobrist_hash(&position.board, position.color, position.castling_rights)
+            })
+            .collect()
+    }
+
+    // Play a move from the current node. Replaying a move that already exists
+    // here just follows it; anything new starts a variation (or extends the
+    // line if this is a leaf).
+    pub fn play(&mut self, move_: Move) -> Result<NodeId, String> {
+        let position = &self.nodes[self.cursor].position;
+        if !get_legal_moves(&position.board, position.color, position.castling_rights).contains(&move_) {
+            return Err("Illegal move".to_string());
+        }
+
+        if let Some(&existing) = self.nodes[self.cursor]
+            .children
+            .iter()
+            .find(|&&child| self.nodes[child].move_ == Some(move_))
+        {
+            self.cursor = existing;
+            return Ok(existing);
+        }
+
+        let mut next = position.clone();
+        next.play(move_);
+        Ok(self.add_child(Some(move_), next))
+    }
+
+    // Analysis-mode pass ("what if I do nothing?"). Not allowed in check, since
+    // the king would be left en prise.
+    pub fn play_null(&mut self) -> Result<NodeId, String> {
+        let position = &self.nodes[self.cursor].position;
+        if is_in_check(&position.board, position.color) {
+            return Err("Cannot pass while in check".to_string());
+        }
+
+        if let Some(&existing) = self.nodes[self.cursor]
+            .children
+            .iter()
+            .find(|&&child| self.nodes[child].move_.is_none())
+        {
+            self.cursor = existing;
+            return Ok(existing);
+        }
+
+        let mut next = position.clone();
+        next.play_null();
+        Ok(self.add_child(None, next))
+    }
+
+    fn add_child(&mut self, move_: Option<Move>, position: FenPosition) -> NodeId {
+        let id = self.nodes.len();
+        self.nodes.push(Node {
+            move_,
+            parent: Some(self.cursor),
+            children: Vec::new(),
+            position,
+        });
+        self.nodes[self.cursor].children.push(id);
+        self.cursor = id;
+        id
+    }
+
+    // Take back the current move: it and everything after it are removed
+    pub fn undo(&mut self) -> bool {
+        self.delete(self.cursor)
+    }
+
+    pub fn go_to(&mut self, node: NodeId) -> bool {
+        if !self.is_attached(node) {
+            return false;
+        }
+        self.cursor = node;
+        true
+    }
+
+    pub fn back(&mut self) -> bool {
+        match self.nodes[self.cursor].parent {
+            Some(parent) => {
+                self.cursor = parent;
+                true
+            }
+            None => false,
+        }
+    }
+
+    // Step along the main continuation of the current node
+    pub fn forward(&mut self) -> bool {
+        match self.nodes[self.cursor].children.first() {
+            Some(&child) => {
+                self.cursor = child;
+                true
+            }
+            None => false,
+        }
+    }
+
+    // Empty for an id that isn't in the tree (any more)
+    pub fn children(&self, node: NodeId) -> &[NodeId] {
+        if !self.is_attached(node) {
+            return &[];
+        }
+        &self.nodes[node].children
+    }
+
+    // Make the line through `node` the main line all the way up to the root
+    pub fn promote_variation(&mut self, node: NodeId) -> bool {
+        if !self.is_attached(node) {
+            return false;
+        }
+        let mut child = node;
+        while let Some(parent) = self.nodes[child].parent {
+            let siblings = &mut self.nodes[parent].children;
+            let index = siblings.iter().position(|&id| id == child).unwrap();
+            let promoted = siblings.remove(index);
+            siblings.insert(0, promoted);
+            child = parent;
+        }
+        true
+    }
+
+    // Remove a node and its subtree; the cursor moves up if it was inside
+    pub fn delete(&mut self, node: NodeId) -> bool {
+        let Some(parent) = self.nodes.get(node).and_then(|n| n.parent) else {
+            return false;
+        };
+        if !self.is_attached(node) {
+            return false;
+        }
+        if self.path(self.cursor).contains(&node) {
+            self.cursor = parent;
+        }
+        self.nodes[parent].children.retain(|&id| id != node);
+        self.nodes[node].parent = None;
+        true
+    }
+
+    // Position after `ply` half-moves along the current line (0 is the start)
+    pub fn position_at(&self, ply: usize) -> Option<FenPosition> {
+        self.path(self.cursor)
+            .get(ply)
+            .map(|&id| self.nodes[id].position.clone())
+    }
+
+    // Every move of the current line in SAN together with the FEN reached after it
+    pub fn move_list(&self) -> Vec<(String, String)> {
+        self.path(self.cursor)
+            .iter()
+            .skip(1)
+            .map(|&id| (self.san(id), to_fen(&self.nodes[id].position)))
+            .collect()
+    }
+
+    pub fn from_pgn(pgn: &PgnGame) -> Result<Self, String> {
+        let mut game = Self::from_fen(pgn.tag("FEN").unwrap_or(START_FEN))?;
+        // Where to return to when each open variation closes
+        let mut resume = Vec::new();
+
+        for token in pgn.tokens() {
+            match token.as_str() {
+                "(" => {
+                    // A variation replaces the move just played
+                    resume.push(game.cursor);
+                    game.back();
+                }
+                ")" => {
+                    game.cursor = resume.pop().ok_or("Unbalanced variation")?;
+                }
+                "--" | "Z0" => {
+                    game.play_null()?;
+                }
+                san => {
+                    let position = game.position();
+                    let move_ = parse_san(&position.board, position.color, position.castling_rights, san)
+                        .ok_or_else(|| format!("Cannot play '{}'", san))?;
+                    game.play(move_)?;
+                }
+            }
+        }
+
+        // Leave the cursor at the end of the main line, the way the game was played
+        game.cursor = ROOT;
+        while game.forward() {}
+        Ok(game)
+    }
+
+    // Export with the full variation tree; tags beyond SetUp/FEN are left to the caller
+    pub fn to_pgn(&self) -> PgnGame {
+        let mut pgn = PgnGame::default();
+        let start = &self.nodes[ROOT].position;
+        let start_fen = to_fen(start);
+        if start_fen != START_FEN {
+            pgn.set_tag("SetUp", "1");
+            pgn.set_tag("FEN", &start_fen);
+        }
+
+        let mut tokens = Vec::new();
+        self.write_line(ROOT, &mut tokens, true);
+        tokens.push("*".to_string());
+        pgn.movetext = tokens.join(" ").replace("( ", "(").replace(" )", ")");
+        pgn
+    }
+
+    // to_pgn with the moves in other piece letters, for a visitor's locale;
+    // files for other software should stay English
+    pub fn to_pgn_localized(&self, letters: &PieceLetters) -> PgnGame {
+        let mut pgn = self.to_pgn();
+        pgn.movetext = letters.localize(&pgn.movetext);
+        pgn
+    }
+
+    fn write_line(&self, from: NodeId, tokens: &mut Vec<String>, mut show_number: bool) {
+        let mut node = from;
+        while let Some(&main) = self.nodes[node].children.first() {
+            tokens.push(self.numbered_san(main, show_number));
+            for &variation in &self.nodes[node].children[1..] {
+                tokens.push("(".to_string());
+                tokens.push(self.numbered_san(variation, true));
+                self.write_line(variation, tokens, false);
+                tokens.push(")".to_string());
+            }
+            // Black's reply after a variation needs its move number again
+            show_number = self.nodes[node].children.len() > 1;
+            node = main;
+        }
+    }
+
+    fn numbered_san(&self, node: NodeId, show_number: bool) -> String {
+        let parent = &self.nodes[self.nodes[node].parent.unwrap()].position;
+        let san = self.san(node);
+        match parent.color {
+            Color::White => format!("{}. {}", parent.fullmove_number, san),
+            Color::Black if show_number => format!("{}... {}", parent.fullmove_number, san),
+            Color::Black => san,
+        }
+    }
+
+    fn san(&self, node: NodeId) -> String {
+        let parent = &self.nodes[self.nodes[node].parent.unwrap()].position;
+        match self.nodes[node].move_ {
+            Some(move_) => move_to_san(&parent.board, parent.color, parent.castling_rights, move_),
+            None => "--".to_string(),
+        }
+    }
+
+    // Node ids from the root down to `node`
+    fn path(&self, node: NodeId) -> Vec<NodeId> {
+        let mut path = vec![node];
+        let mut current = node;
+        while let Some(parent) = self.nodes[current].parent {
+            path.push(parent);
+            current = parent;
+        }
+        path.reverse();
+        path
+    }
+
+    fn is_attached(&self, node: NodeId) -> bool {
+        node < self.nodes.len() && self.path(node)[0] == ROOT
+    }
+}
@@ -0,0 +1,155 @@
+use crate::chess::engine::{get_best_move, get_legal_moves, get_opponent, make_move, CastlingRights};
+use crate::chess::fen::{parse_fen, START_FEN};
+use crate::chess::pieces::{Color, Move, Square};
+use std::fmt;
+use std::sync::{Mutex, MutexGuard, TryLockError};
+
+#[derive(Debug, PartialEq)]
+pub enum EngineError {
+    // Another call is still working on the position
+    SearchInProgress,
+    IllegalMove,
+}
+
+impl fmt::Display for EngineError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EngineError::SearchInProgress => write!(f, "SearchInProgress"),
+            EngineError::IllegalMove => write!(f, "IllegalMove"),
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct EngineState {
+    pub board: [[i8; 8]; 8],
+    pub color: Color,
+    pub castling_rights: CastlingRights,
+}
+
+// Shared engine object for callers that may overlap (interleaved async JS, or
+// several threads natively). Every call takes the lock without waiting, so a
+// call that arrives mid-search fails with SearchInProgress instead of
+// blocking or seeing a half-updated position.
+pub struct EngineHandle {
+    state: Mutex<EngineState>,
+}
+
+impl Default for EngineHandle {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl EngineHandle {
+    pub fn new() -> Self {
+        let start = parse_fen(START_FEN).unwrap();
+        EngineHandle {
+            state: Mutex::new(EngineState {
+                board: start.board,
+                color: start.color,
+                castling_rights: start.castling_rights,
+            }),
+        }
+    }
+
+    fn lock(&self) -> Result<MutexGuard<'_, EngineState>, EngineError> {
+        match self.state.try_lock() {
+            Ok(state) => Ok(state),
+            Err(TryLockError::WouldBlock) => Err(EngineError::SearchInProgress),
+            // A panic mid-call can't leave the state half-written: it is only
+            // ever replaced wholesale
+            Err(TryLockError::Poisoned(poisoned)) => Ok(poisoned.into_inner()),
+        }
+    }
+
+    pub fn state(&self) -> Result<EngineState, EngineError> {
+        Ok(*self.lock()?)
+    }
+
+    pub fn set_position(&self, new_state: EngineState) -> Result<(), EngineError> {
+        *self.lock()? = new_state;
+        Ok(())
+    }
+
+    pub fn play_move(&self, move_: Move) -> Result<(), EngineError> {
+        let mut state = self.lock()?;
+        if !get_legal_moves(&state.board, state.color, state.castling_rights).contains(&move_) {
+            return Err(EngineError::IllegalMove);
+        }
+
+        let mut next = *state;
+        let (_, new_rights) = make_move(&mut next.board, move_, state.castling_rights);
+        next.castling_rights = new_rights;
+        next.color = get_opponent(state.color);
+        *state = next;
+        Ok(())
+    }
+
+    // Holds the lock for the whole search
+    pub fn best_move(
+        &self,
+        depth: i32,
+        use_pruning: bool,
+        use_move_ordering: bool,
+    ) -> Result<Option<(Square, Square, u32)>, EngineError> {
+        let state = self.lock()?;
+        Ok(get_best_move(
+            &state.board,
+            state.color,
+            depth,
+            state.castling_rights,
+            use_pruning,
+            use_move_ordering,
+            None,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chess::fen::parse_square;
+
+    fn mv(from: &str, to: &str) -> Move {
+        (parse_square(from).unwrap(), parse_square(to).unwrap())
+    }
+
+    #[test]
+    fn calls_fail_while_another_holds_the_lock() {
+        let handle = EngineHandle::new();
+        let guard = handle.lock().unwrap();
+        assert_eq!(handle.state().err(), Some(EngineError::SearchInProgress));
+        assert_eq!(handle.play_move(mv("e2", "e4")), Err(EngineError::SearchInProgress));
+        assert_eq!(handle.best_move(1, true, true).err(), Some(EngineError::SearchInProgress));
+        drop(guard);
+
+        assert_eq!(handle.play_move(mv("e2", "e4")), Ok(()));
+        assert_eq!(handle.state().unwrap().color, Color::Black);
+    }
+
+    #[test]
+    fn illegal_moves_leave_the_position() {
+        let handle = EngineHandle::new();
+        let before = handle.state().unwrap().board;
+        assert_eq!(handle.play_move(mv("e2", "e5")), Err(EngineError::IllegalMove));
+        assert_eq!(handle.state().unwrap().board, before);
+        assert_eq!(handle.state().unwrap().color, Color::White);
+    }
+
+    #[test]
+    fn a_panic_does_not_poison_the_handle() {
+        let handle = EngineHandle::new();
+        std::thread::scope(|scope| {
+            let result = scope
+                .spawn(|| {
+                    let _guard = handle.lock().unwrap();
+                    panic!("mid-call");
+                })
+                .join();
+            assert!(result.is_err());
+        });
+        assert!(handle.state().is_ok());
+        assert!(handle.best_move(1, true, true).unwrap().is_some());
+    }
+}
@@ -1,2 +1,17 @@
+pub mod clock;
+pub mod diff;
 pub mod engine;
+pub mod eval;
+pub mod fen;
+pub mod game;
+pub mod handle;
+pub mod notation;
+pub mod pgn;
 pub mod pieces;
+pub(crate) mod pst;
+pub mod simd;
+pub mod status;
+pub(crate) mod tables;
+pub(crate) mod timing;
+
+pub use tables::zobrist_hash;
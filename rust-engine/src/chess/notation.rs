@@ -0,0 +1,315 @@
+use crate::chess::engine::{get_legal_moves, get_opponent, is_in_check, make_move, CastlingRights};
+use crate::chess::fen::{parse_square, square_name};
+use crate::chess::pieces::{Color, Piece, PieceType, Square, E, WK, WP};
+
+// Resolve a SAN move ("Nbd7", "exd5", "O-O", "Qh4#") against the legal moves of
+// the position. Promotions and en passant aren't supported by the engine yet,
+// so those moves come back as None.
+pub fn parse_san(
+    board: &[[i8; 8]; 8],
+    color: Color,
+    castling_rights: CastlingRights,
+    san: &str,
+) -> Option<(Square, Square)> {
+    let san = san.trim_end_matches(['+', '#', '!', '?']);
+    let legal_moves = get_legal_moves(board, color, castling_rights);
+    let home_rank = if color == Color::White { 7 } else { 0 };
+
+    if san == "O-O" || san == "0-0" {
+        let castle = (Square::new(home_rank, 4), Square::new(home_rank, 6));
+        return legal_moves.contains(&castle).then_some(castle);
+    }
+    if san == "O-O-O" || san == "0-0-0" {
+        let castle = (Square::new(home_rank, 4), Square::new(home_rank, 2));
+        return legal_moves.contains(&castle).then_some(castle);
+    }
+    if san.contains('=') {
+        return None;
+    }
+
+    let mut chars: Vec<char> = san.chars().collect();
+    // Pawn moves have no letter, so a leading 'P' isn't read as one
+    let letter = chars.first().and_then(|&c| PieceType::from_letter(c));
+    let piece_type = match letter.filter(|&kind| kind != PieceType::Pawn) {
+        Some(kind) => {
+            chars.remove(0);
+            kind.code()
+        }
+        None => WP,
+    };
+    chars.retain(|&c| c != 'x' && c != '-');
+    if chars.len() < 2 {
+        return None;
+    }
+
+    let target: String = chars[chars.len() - 2..].iter().collect();
+    let to = parse_square(&target)?;
+
+    // Whatever is left before the target square disambiguates the origin
+    let mut from_file = None;
+    let mut from_rank = None;
+    for &c in &chars[..chars.len() - 2] {
+        if ('a'..='h').contains(&c) {
+            from_file = Some(c as usize - 'a' as usize);
+        } else if ('1'..='8').contains(&c) {
+            from_rank = Some(8 - c.to_digit(10)? as usize);
+        } else {
+            return None;
+        }
+    }
+
+    let mut candidates = legal_moves.into_iter().filter(|&(from, dest)| {
+        dest == to
+            && board[from].abs() == piece_type
+            && from_file.is_none_or(|file| file == from.file())
+            && from_rank.is_none_or(|rank| rank == from.rank())
+    });
+
+    let found = candidates.next()?;
+    if candidates.next().is_some() {
+        // Ambiguous
+        return None;
+    }
+    Some(found)
+}
+
+// Forgiving move input for the "type your move" box and the CLI. Accepts SAN
+// ("e4", "Nf3", "exd5"), coordinates with or without a piece letter or dash
+// ("e2e4", "e2-e4", "Pe2-e4", "Ng1xf3"), castling written with O or 0 in any
+// case, casual capitalization ("nf3", "NF3"), and trailing check or
+// annotation marks ("Qh4#", "Nf3!?"). Returns None unless exactly one legal
+// move fits.
+pub fn parse_move_lenient(
+    board: &[[i8; 8]; 8],
+    color: Color,
+    castling_rights: CastlingRights,
+    input: &str,
+) -> Option<(Square, Square)> {
+    let cleaned: String = input
+        .trim()
+        .trim_end_matches(['+', '#', '!', '?'])
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .collect();
+    if cleaned.is_empty() {
+        return None;
+    }
+
+    let castle = cleaned.to_ascii_uppercase().replace('0', "O").replace('-', "");
+    if castle == "OO" {
+        return parse_san(board, color, castling_rights, "O-O");
+    }
+    if castle == "OOO" {
+        return parse_san(board, color, castling_rights, "O-O-O");
+    }
+
+    // Coordinates, optionally with a piece letter and separators
+    let mut coords: String = cleaned
+        .chars()
+        .filter(|c| !matches!(c, '-' | 'x' | 'X' | ':'))
+        .collect::<String>()
+        .to_ascii_lowercase();
+    if coords.len() == 5 && coords.starts_with(['p', 'n', 'b', 'r', 'q', 'k']) {
+        coords.remove(0);
+    }
+    if coords.len() == 4 {
+        if let (Some(from), Some(to)) = (parse_square(&coords[..2]), parse_square(&coords[2..])) {
+            let legal_moves = get_legal_moves(board, color, castling_rights);
+            return legal_moves.contains(&(from, to)).then_some((from, to));
+        }
+    }
+
+    // SAN. An uppercase piece letter is always a piece; anything else is read
+    // as a pawn file first (so "bxc4" stays a pawn capture) and then as a piece.
+    let first = cleaned.chars().next()?;
+    let rest = cleaned[first.len_utf8()..].to_ascii_lowercase();
+    let as_piece = format!("{}{}", first.to_ascii_uppercase(), rest);
+
+    if matches!(first, 'N' | 'B' | 'R' | 'Q' | 'K') {
+        return parse_san(board, color, castling_rights, &as_piece);
+    }
+    if first == 'p' || first == 'P' {
+        return parse_san(board, color, castling_rights, &rest);
+    }
+    let as_pawn = format!("{}{}", first.to_ascii_lowercase(), rest);
+    parse_san(board, color, castling_rights, &as_pawn)
+        .or_else(|| parse_san(board, color, castling_rights, &as_piece))
+}
+
+// Standard algebraic notation for a legal move, including check and mate marks
+pub fn move_to_san(
+    board: &[[i8; 8]; 8],
+    color: Color,
+    castling_rights: CastlingRights,
+    move_: (Square, Square),
+) -> String {
+    let (from, to) = move_;
+    let piece = board[from];
+    let piece_type = piece.abs();
+
+    let mut san = if piece_type == WK && from.file().abs_diff(to.file()) == 2 {
+        if to.file() == 6 { "O-O" } else { "O-O-O" }.to_string()
+    } else {
+        let is_capture = board[to] != E;
+        let mut san = String::new();
+        if piece_type == WP {
+            if is_capture {
+                san.push((b'a' + from.file() as u8) as char);
+            }
+        } else {
+            san.push(PieceType::from_code(piece_type).map_or('P', PieceType::letter));
+
+            // Disambiguate against other pieces of the same type reaching the square
+            let rivals: Vec<Square> = get_legal_moves(board, color, castling_rights)
+                .into_iter()
+                .filter(|&(rival_from, rival_to)| rival_to == to && rival_from != from)
+                .filter(|&(rival_from, _)| board[rival_from] == piece)
+                .map(|(rival_from, _)| rival_from)
+                .collect();
+            if !rivals.is_empty() {
+                let name = square_name(from);
+                if rivals.iter().all(|rival| rival.file() != from.file()) {
+                    san.push_str(&name[..1]);
+                } else if rivals.iter().all(|rival| rival.rank() != from.rank()) {
+                    san.push_str(&name[1..]);
+                } else {
+                    san.push_str(&name);
+                }
+            }
+        }
+        if is_capture {
+            san.push('x');
+        }
+        san.push_str(&square_name(to));
+        san
+    };
+
+    let mut after = *board;
+    let (_, new_rights) = make_move(&mut after, move_, castling_rights);
+    let opponent = get_opponent(color);
+    if is_in_check(&after, opponent) {
+        san.push(if get_legal_moves(&after, opponent, new_rights).is_empty() { '#' } else { '+' });
+    }
+    san
+}
+
+// Piece letters for displaying SAN: English for PGN files, figurines or a
+// locale's own letters for the on-site move list. Indexed pawn to king.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct PieceLetters([char; 6]);
+
+impl PieceLetters {
+    pub const ENGLISH: PieceLetters = PieceLetters(['P', 'N', 'B', 'R', 'Q', 'K']);
+
+    pub fn figurine() -> PieceLetters {
+        PieceLetters(PieceType::ALL.map(|kind| Piece::new(Color::White, kind).symbol()))
+    }
+
+    // Six letters in pawn, knight, bishop, rook, queen, king order ("BSLTDK")
+    pub fn from_letters(letters: &str) -> Option<PieceLetters> {
+        let letters: Vec<char> = letters.chars().collect();
+        letters.try_into().ok().map(PieceLetters)
+    }
+
+    // "en", "figurine", a language code with its own letters, or six letters
+    pub fn from_name(name: &str) -> Option<PieceLetters> {
+        match name {
+            "en" => Some(PieceLetters::ENGLISH),
+            "figurine" => Some(PieceLetters::figurine()),
+            "de" => PieceLetters::from_letters("BSLTDK"),
+            "es" => PieceLetters::from_letters("PCATDR"),
+            "fr" => PieceLetters::from_letters("PCFTDR"),
+            "it" => PieceLetters::from_letters("PCATDR"),
+            "tr" => PieceLetters::from_letters("PAFKVŞ"),
+            _ => PieceLetters::from_letters(name),
+        }
+    }
+
+    pub fn letter(&self, kind: PieceType) -> char {
+        self.0[kind.code() as usize - 1]
+    }
+
+    // Rewrite English SAN or movetext. Upper-case letters other than the O of
+    // castling only ever name pieces there, so they can be swapped one by one.
+    pub fn localize(&self, san: &str) -> String {
+        san.chars()
+            .map(|c| PieceType::from_letter(c).map_or(c, |kind| self.letter(kind)))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chess::fen::{parse_fen, START_FEN};
+
+    // "e2e4" as a move
+    fn coords(text: &str) -> Option<(Square, Square)> {
+        Some((parse_square(&text[..2]).unwrap(), parse_square(&text[2..]).unwrap()))
+    }
+
+    fn lenient(fen: &str, input: &str) -> Option<(Square, Square)> {
+        let position = parse_fen(fen).unwrap();
+        parse_move_lenient(&position.board, position.color, position.castling_rights, input)
+    }
+
+    fn san(fen: &str, text: &str) -> Option<(Square, Square)> {
+        let position = parse_fen(fen).unwrap();
+        parse_san(&position.board, position.color, position.castling_rights, text)
+    }
+
+    #[test]
+    fn lenient_input_reaches_the_same_move() {
+        for input in ["e4", "Pe2-e4", "e2e4", "e2-e4", "E2E4", " e4 ", "e4!"] {
+            assert_eq!(lenient(START_FEN, input), coords("e2e4"), "{}", input);
+        }
+        for input in ["Nf3", "nf3", "NF3", "Nf3!?", "g1f3", "Ng1-f3", "Ng1xf3"] {
+            assert_eq!(lenient(START_FEN, input), coords("g1f3"), "{}", input);
+        }
+        for input in ["e5", "Nf4", "e2e5", "", "hello"] {
+            assert_eq!(lenient(START_FEN, input), None, "{}", input);
+        }
+    }
+
+    #[test]
+    fn lenient_castling_in_any_spelling() {
+        let fen = "r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1";
+        for input in ["O-O", "0-0", "o-o", "OO", "e1g1"] {
+            assert_eq!(lenient(fen, input), coords("e1g1"), "{}", input);
+        }
+        for input in ["O-O-O", "0-0-0", "o-o-o+"] {
+            assert_eq!(lenient(fen, input), coords("e1c1"), "{}", input);
+        }
+    }
+
+    #[test]
+    fn lowercase_b_is_a_pawn_before_a_bishop() {
+        let fen = "4k3/8/8/8/2p5/1P6/8/4KB2 w - - 0 1";
+        assert_eq!(lenient(fen, "bxc4"), coords("b3c4"));
+        assert_eq!(lenient(fen, "Bxc4"), coords("f1c4"));
+    }
+
+    #[test]
+    fn san_disambiguates_and_rejects_ambiguity() {
+        let fen = "rnbqkb1r/ppp1pppp/5n2/3p4/3P4/5N2/PPP1PPPP/RNBQKB1R b KQkq - 2 2";
+        assert_eq!(san(fen, "Nbd7"), coords("b8d7"));
+        assert_eq!(san(fen, "Nfd7"), coords("f6d7"));
+        assert_eq!(san(fen, "Nd7"), None);
+        // Promotions aren't supported yet
+        assert_eq!(san("8/P6k/8/8/8/8/8/K7 w - - 0 1", "a8=Q"), None);
+    }
+
+    #[test]
+    fn san_round_trips_and_localizes() {
+        let fen = "rnbqkbnr/pppp1ppp/8/4p3/6P1/5P2/PPPPP2P/RNBQKBNR b KQkq - 0 2";
+        let position = parse_fen(fen).unwrap();
+        let move_ = san(fen, "Qh4#").unwrap();
+        let text = move_to_san(&position.board, position.color, position.castling_rights, move_);
+        assert_eq!(text, "Qh4#");
+
+        assert_eq!(PieceLetters::from_name("de").unwrap().localize(&text), "Dh4#");
+        assert_eq!(PieceLetters::from_name("figurine").unwrap().localize("Nf3 O-O"), "♘f3 O-O");
+        assert_eq!(PieceLetters::from_name("fr").unwrap().localize("1. e4 e5 2. Nf3 Nc6 3. Bb5"), "1. e4 e5 2. Cf3 Cc6 3. Fb5");
+        assert_eq!(PieceLetters::from_name("BSL"), None);
+    }
+}
@@ -0,0 +1,427 @@
+// Incremental PGN reader: feed it text in arbitrary chunks and collect the games
+// that have been completed so far. Nothing holds more than one game in memory,
+// so multi-megabyte databases can be walked natively or from the browser.
+
+use crate::chess::engine::evaluate_board;
+use crate::chess::fen::{fnv1a, parse_fen, FenPosition, START_FEN};
+use crate::chess::notation::parse_san;
+use crate::chess::pieces::Color;
+use crate::chess::status::Termination;
+
+#[derive(Clone, Debug, Default)]
+pub struct PgnGame {
+    pub tags: Vec<(String, String)>,
+    pub movetext: String,
+}
+
+impl PgnGame {
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        self.tags
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    pub fn set_tag(&mut self, name: &str, value: &str) {
+        match self.tags.iter_mut().find(|(key, _)| key == name) {
+            Some((_, existing)) => *existing = value.to_string(),
+            None => self.tags.push((name.to_string(), value.to_string())),
+        }
+    }
+
+    // Record how the game ended in the Termination and Result tags, and make
+    // sure the movetext closes with the same result
+    pub fn set_termination(&mut self, termination: Termination, winner: Option<Color>) {
+        let result = match winner {
+            _ if termination.is_draw() => "1/2-1/2",
+            Some(Color::White) => "1-0",
+            Some(Color::Black) => "0-1",
+            None => "1/2-1/2",
+        };
+        self.set_tag("Termination", termination.description());
+        self.set_tag("Result", result);
+
+        let movetext = self.movetext.trim_end();
+        let without_result = match movetext.rsplit_once(char::is_whitespace) {
+            Some((rest, last)) if is_result(last) => rest.trim_end(),
+            None if is_result(movetext) => "",
+            _ => movetext,
+        };
+        self.movetext = format!("{} {}", without_result, result).trim_start().to_string();
+    }
+
+    // Main-line move tokens in order, with move numbers, comments, variations,
+    // NAGs and the result marker stripped out
+    pub fn moves(&self) -> Vec<String> {
+        let mut moves = Vec::new();
+        let mut variation_depth = 0;
+        for token in self.tokens() {
+            match token.as_str() {
+                "(" => variation_depth += 1,
+                ")" => variation_depth -= 1,
+                _ if variation_depth == 0 => moves.push(token),
+                _ => {}
+            }
+        }
+        moves
+    }
+
+    // Move tokens including variations, which are bracketed by "(" and ")"
+    // tokens. Comments, move numbers, NAGs and the result are dropped.
+    pub fn tokens(&self) -> Vec<String> {
+        let mut tokens = Vec::new();
+        let mut comment_depth = 0;
+        let mut variation_depth = 0;
+        let mut line_comment = false;
+        let mut token = String::new();
+
+        let flush = |token: &mut String, tokens: &mut Vec<String>| {
+            let word = strip_move_number(token);
+            if !word.is_empty() && !word.starts_with('$') && !is_result(token) {
+                tokens.push(word.to_string());
+            }
+            token.clear();
+        };
+
+        for c in self.movetext.chars() {
+            if line_comment {
+                if c == '\n' {
+                    line_comment = false;
+                }
+                continue;
+            }
+            match c {
+                '{' => {
+                    flush(&mut token, &mut tokens);
+                    comment_depth += 1;
+                }
+                '}' if comment_depth > 0 => comment_depth -= 1,
+                _ if comment_depth > 0 => {}
+                ';' => {
+                    flush(&mut token, &mut tokens);
+                    line_comment = true;
+                }
+                '(' => {
+                    flush(&mut token, &mut tokens);
+                    variation_depth += 1;
+                    tokens.push("(".to_string());
+                }
+                ')' if variation_depth > 0 => {
+                    flush(&mut token, &mut tokens);
+                    variation_depth -= 1;
+                    tokens.push(")".to_string());
+                }
+                _ if c.is_whitespace() => flush(&mut token, &mut tokens),
+                _ => token.push(c),
+            }
+        }
+        if comment_depth == 0 {
+            flush(&mut token, &mut tokens);
+        }
+        // Close variations left open by a truncated game
+        tokens.extend(std::iter::repeat_n(")".to_string(), variation_depth));
+        tokens
+    }
+
+    // Position before the first move and after every ply. Replay stops at the
+    // first move that can't be resolved (bad SAN, or rules the engine lacks).
+    pub fn positions(&self) -> Vec<FenPosition> {
+        let start = match self.tag("FEN") {
+            Some(fen) => parse_fen(fen),
+            None => parse_fen(START_FEN),
+        };
+        let Ok(mut position) = start else {
+            return Vec::new();
+        };
+
+        let mut positions = vec![position.clone()];
+        for san in self.moves() {
+            let Some(move_) = parse_san(&position.board, position.color, position.castling_rights, &san)
+            else {
+                break;
+            };
+            position.play(move_);
+            positions.push(position.clone());
+        }
+        positions
+    }
+
+    // FNV-1a over the starting position and the move list with check/annotation
+    // marks removed, so the same game typed up by two sources hashes the same
+    pub fn move_hash(&self) -> u64 {
+        let mut normalized = self.tag("FEN").unwrap_or(START_FEN).to_string();
+        for san in self.moves() {
+            normalized.push(' ');
+            normalized.push_str(san.trim_end_matches(['+', '#', '!', '?']));
+        }
+        fnv1a(normalized.as_bytes())
+    }
+
+    pub fn to_pgn(&self) -> String {
+        let mut out = String::new();
+        for (key, value) in &self.tags {
+            out.push_str(&format!("[{} \"{}\"]\n", key, value.replace('"', "\\\"")));
+        }
+        out.push('\n');
+        out.push_str(self.movetext.trim());
+        out.push_str("\n\n");
+        out
+    }
+}
+
+// "12." or "12..." in front of a move ("12.Nf3", "12...Nf6"), or on its own.
+// Only digits followed by dots count, so castling with zeros ("0-0") is left alone.
+fn strip_move_number(token: &str) -> &str {
+    let after_digits = token.trim_start_matches(|c: char| c.is_ascii_digit());
+    if after_digits.len() < token.len() && after_digits.starts_with('.') {
+        after_digits.trim_start_matches('.')
+    } else {
+        token
+    }
+}
+
+fn is_result(token: &str) -> bool {
+    matches!(token, "1-0" | "0-1" | "1/2-1/2" | "*")
+}
+
+fn parse_tag(line: &str) -> Option<(String, String)> {
+    let inner = line.trim().strip_prefix('[')?.strip_suffix(']')?;
+    let (key, rest) = inner.split_once(char::is_whitespace)?;
+    let value = rest.trim().strip_prefix('"')?.strip_suffix('"')?;
+    Some((key.to_string(), value.replace("\\\"", "\"")))
+}
+
+#[derive(Default)]
+pub struct PgnReader {
+    // Trailing text of the last chunk that didn't end with a newline yet
+    pending: String,
+    current: PgnGame,
+    in_movetext: bool,
+    comment_depth: usize,
+}
+
+impl PgnReader {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Push more text in; returns every game completed by this chunk
+    pub fn feed(&mut self, chunk: &str) -> Vec<PgnGame> {
+        let mut text = std::mem::take(&mut self.pending);
+        text.push_str(chunk);
+        let mut games = Vec::new();
+
+        // Walk the lines in place and cut the consumed text off once at the
+        // end, so a big chunk isn't shifted down after every line
+        let mut start = 0;
+        while let Some(end) = text[start..].find('\n') {
+            let line = &text[start..start + end];
+            if let Some(game) = self.process_line(line.trim_end_matches('\r')) {
+                games.push(game);
+            }
+            start += end + 1;
+        }
+        text.drain(..start);
+        self.pending = text;
+        games
+    }
+
+    // Flush whatever is left once the input is exhausted
+    pub fn finish(mut self) -> Option<PgnGame> {
+        let rest = std::mem::take(&mut self.pending);
+        if let Some(game) = self.process_line(&rest) {
+            return Some(game);
+        }
+        self.take_game()
+    }
+
+    fn process_line(&mut self, line: &str) -> Option<PgnGame> {
+        let trimmed = line.trim();
+
+        // A tag line outside of a comment starts a new game if we were already in movetext
+        if self.comment_depth == 0 && trimmed.starts_with('[') {
+            if let Some(tag) = parse_tag(trimmed) {
+                let finished = if self.in_movetext { self.take_game() } else { None };
+                self.current.tags.push(tag);
+                return finished;
+            }
+        }
+
+        if trimmed.is_empty() && self.comment_depth == 0 {
+            return None;
+        }
+
+        self.in_movetext = true;
+        for c in line.chars() {
+            match c {
+                '{' => self.comment_depth += 1,
+                '}' => self.comment_depth = self.comment_depth.saturating_sub(1),
+                _ => {}
+            }
+        }
+        if !self.current.movetext.is_empty() {
+            self.current.movetext.push('\n');
+        }
+        self.current.movetext.push_str(line);
+
+        // The result marker closes the game, unless it is sitting inside a comment
+        if self.comment_depth == 0 {
+            if let Some(last) = trimmed.split_whitespace().last() {
+                if is_result(last) {
+                    return self.take_game();
+                }
+            }
+        }
+        None
+    }
+
+    fn take_game(&mut self) -> Option<PgnGame> {
+        self.in_movetext = false;
+        self.comment_depth = 0;
+        let game = std::mem::take(&mut self.current);
+        if game.tags.is_empty() && game.movetext.trim().is_empty() {
+            None
+        } else {
+            Some(game)
+        }
+    }
+}
+
+// Remembers the move hashes of the games it has seen
+#[derive(Default)]
+pub struct PgnDeduper {
+    seen: std::collections::HashSet<u64>,
+}
+
+impl PgnDeduper {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn is_duplicate(&mut self, game: &PgnGame) -> bool {
+        !self.seen.insert(game.move_hash())
+    }
+}
+
+// Criteria for picking games out of a database; unset fields match everything
+#[derive(Default)]
+pub struct PgnFilter {
+    // Prefix match, so "B2" selects B20-B29
+    pub eco: Option<String>,
+    // Case-insensitive substring of either player name
+    pub player: Option<String>,
+    pub result: Option<String>,
+    // Game must reach a material difference of at least this many pawns
+    pub min_imbalance: Option<i32>,
+    // Game must pass through this position (side to move and castling rights included)
+    pub position: Option<FenPosition>,
+}
+
+impl PgnFilter {
+    pub fn matches(&self, game: &PgnGame) -> bool {
+        if let Some(eco) = &self.eco {
+            if !game.tag("ECO").is_some_and(|value| value.starts_with(eco.as_str())) {
+                return false;
+            }
+        }
+
+        if let Some(player) = &self.player {
+            let player = player.to_lowercase();
+            let plays = |tag| game.tag(tag).is_some_and(|name| name.to_lowercase().contains(&player));
+            if !plays("White") && !plays("Black") {
+                return false;
+            }
+        }
+
+        if let Some(result) = &self.result {
+            if game.tag("Result") != Some(result.as_str()) {
+                return false;
+            }
+        }
+
+        if self.min_imbalance.is_none() && self.position.is_none() {
+            return true;
+        }
+
+        let positions = game.positions();
+        if let Some(min_imbalance) = self.min_imbalance {
+            if !positions
+                .iter()
+                .any(|p| evaluate_board(&p.board).abs() >= min_imbalance)
+            {
+                return false;
+            }
+        }
+        if let Some(target) = &self.position {
+            if !positions.iter().any(|p| {
+                p.board == target.board
+                    && p.color == target.color
+                    && p.castling_rights == target.castling_rights
+            }) {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chess::pieces::{BK, WK};
+
+    fn game(movetext: &str) -> PgnGame {
+        PgnGame {
+            tags: Vec::new(),
+            movetext: movetext.to_string(),
+        }
+    }
+
+    #[test]
+    fn replays_castling_written_with_zeros() {
+        let game = game("1. e4 e5 2. Nf3 Nc6 3. Bc4 Bc5 4. 0-0 Nf6 5. d3 d6 6. Bg5 h6 7. Bh4 Bd7 8. Nc3 Qe7 9. a3 0-0-0 *");
+        assert_eq!(game.moves()[6], "0-0");
+        assert_eq!(game.moves()[17], "0-0-0");
+
+        let positions = game.positions();
+        assert_eq!(positions.len(), 19);
+        let last = positions.last().unwrap();
+        assert_eq!(last.board[7][6], WK);
+        assert_eq!(last.board[0][2], BK);
+    }
+
+    #[test]
+    fn tokens_drop_numbers_comments_nags_and_result() {
+        let game = game("1. e4 {best by test} e5 $1 2.Nf3 ; to the end of the line\n2... Nc6 3...a6 1-0");
+        assert_eq!(game.tokens(), ["e4", "e5", "Nf3", "Nc6", "a6"]);
+    }
+
+    #[test]
+    fn tokens_bracket_variations_and_moves_skip_them() {
+        let game = game("1. e4 (1. d4 d5 (1... Nf6)) 1... e5 (1... c5 {Sicilian}) 2. Nf3 *");
+        assert_eq!(game.tokens(), ["e4", "(", "d4", "d5", "(", "Nf6", ")", ")", "e5", "(", "c5", ")", "Nf3"]);
+        assert_eq!(game.moves(), ["e4", "e5", "Nf3"]);
+    }
+
+    #[test]
+    fn tokens_close_a_truncated_variation() {
+        let game = game("1. e4 e5 (1... c5 2. Nf3");
+        assert_eq!(game.tokens(), ["e4", "e5", "(", "c5", "Nf3", ")"]);
+    }
+
+    #[test]
+    fn reader_splits_games_across_chunks() {
+        let mut reader = PgnReader::new();
+        let mut games = reader.feed("[Event \"One\"]\n\n1. e4 e5 {a comment\n[with a bracket]} 1-0\n[Eve");
+        assert_eq!(games.len(), 1);
+        games.extend(reader.feed("nt \"Two\"]\n\n1. d4 d5"));
+        assert_eq!(games.len(), 1);
+        games.extend(reader.finish());
+
+        assert_eq!(games.len(), 2);
+        assert_eq!(games[0].tag("Event"), Some("One"));
+        assert_eq!(games[0].moves(), ["e4", "e5"]);
+        assert_eq!(games[1].tag("Event"), Some("Two"));
+        assert_eq!(games[1].moves(), ["d4", "d5"]);
+    }
+}
@@ -1,3 +1,5 @@
+use crate::chess::tables::{SquareList, ALL_DIRECTIONS, DIAGONAL, KING_TARGETS, KNIGHT_TARGETS, ORTHOGONAL, RAYS};
+
 pub const E: i8 = 0; // Empty
 
 pub const WP: i8 = 1; // White Pawn
@@ -14,13 +16,204 @@ pub const BR: i8 = -4; // Black Rook
 pub const BQ: i8 = -5; // Black Queen
 pub const BK: i8 = -6; // Black King
 
-#[derive(PartialEq, Copy, Clone)]
+// A square of the board, rank * 8 + file with rank 0 the eighth rank (row 0
+// of the board arrays). Only built from in-range coordinates, so boards index
+// by it directly: board[square].
+#[derive(PartialEq, Eq, PartialOrd, Ord, Hash, Copy, Clone, Debug)]
+pub struct Square(u8);
+
+impl Square {
+    pub const fn new(rank: usize, file: usize) -> Square {
+        assert!(rank < 8 && file < 8, "square out of range");
+        Square((rank * 8 + file) as u8)
+    }
+
+    // None when either coordinate is off the board
+    pub const fn try_new(rank: isize, file: isize) -> Option<Square> {
+        if rank >= 0 && rank < 8 && file >= 0 && file < 8 {
+            Some(Square::new(rank as usize, file as usize))
+        } else {
+            None
+        }
+    }
+
+    // rank * 8 + file, the layout of the flat boards the JS side passes
+    pub fn from_index(index: usize) -> Option<Square> {
+        (index < 64).then_some(Square(index as u8))
+    }
+
+    pub const fn rank(self) -> usize {
+        (self.0 / 8) as usize
+    }
+
+    pub const fn file(self) -> usize {
+        (self.0 % 8) as usize
+    }
+
+    pub const fn index(self) -> usize {
+        self.0 as usize
+    }
+
+    // The square dr ranks and df files away, if that is still on the board
+    pub const fn offset(self, dr: isize, df: isize) -> Option<Square> {
+        Square::try_new(self.rank() as isize + dr, self.file() as isize + df)
+    }
+
+    // The same square seen from the other side: rank 0 swaps with rank 7
+    pub const fn flip(self) -> Square {
+        Square::new(7 - self.rank(), self.file())
+    }
+
+    // a8, b8, ... h1: the order of the board arrays
+    pub fn all() -> impl Iterator<Item = Square> {
+        (0..64).map(|index| Square(index as u8))
+    }
+}
+
+impl<T> std::ops::Index<Square> for [[T; 8]; 8] {
+    type Output = T;
+
+    fn index(&self, square: Square) -> &T {
+        &self[square.rank()][square.file()]
+    }
+}
+
+impl<T> std::ops::IndexMut<Square> for [[T; 8]; 8] {
+    fn index_mut(&mut self, square: Square) -> &mut T {
+        &mut self[square.rank()][square.file()]
+    }
+}
+
+pub type Move = (Square, Square);
+
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
 pub enum Color {
     White,
     Black,
 }
 
-fn get_piece_color(piece: i8) -> Color {
+impl Color {
+    // The JS side passes colors as ints: 0 is white, anything else black
+    pub fn from_int(color_int: i32) -> Color {
+        if color_int == 0 {
+            Color::White
+        } else {
+            Color::Black
+        }
+    }
+
+    pub fn to_int(self) -> i32 {
+        match self {
+            Color::White => 0,
+            Color::Black => 1,
+        }
+    }
+}
+
+// Typed view of the i8 piece codes above, for callers that would rather not
+// match on numbers. Boards keep the i8 encoding; convert at the edges with
+// Piece::try_from(code) and i8::from(piece).
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+pub enum PieceType {
+    Pawn,
+    Knight,
+    Bishop,
+    Rook,
+    Queen,
+    King,
+}
+
+impl PieceType {
+    pub const ALL: [PieceType; 6] = [
+        PieceType::Pawn,
+        PieceType::Knight,
+        PieceType::Bishop,
+        PieceType::Rook,
+        PieceType::Queen,
+        PieceType::King,
+    ];
+
+    // The white piece code, WP..WK; black codes are the negatives
+    pub fn code(self) -> i8 {
+        match self {
+            PieceType::Pawn => WP,
+            PieceType::Knight => WN,
+            PieceType::Bishop => WB,
+            PieceType::Rook => WR,
+            PieceType::Queen => WQ,
+            PieceType::King => WK,
+        }
+    }
+
+    // Either color's code; None for E or anything out of range
+    pub fn from_code(code: i8) -> Option<PieceType> {
+        PieceType::ALL.into_iter().find(|kind| kind.code() == code.abs())
+    }
+
+    // Upper-case SAN/FEN letter
+    pub fn letter(self) -> char {
+        ['P', 'N', 'B', 'R', 'Q', 'K'][self.code() as usize - 1]
+    }
+
+    pub fn from_letter(c: char) -> Option<PieceType> {
+        PieceType::ALL.into_iter().find(|kind| kind.letter() == c)
+    }
+}
+
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+pub struct Piece {
+    pub color: Color,
+    pub kind: PieceType,
+}
+
+impl Piece {
+    pub fn new(color: Color, kind: PieceType) -> Piece {
+        Piece { color, kind }
+    }
+
+    // FEN letter: upper case for white, lower case for black
+    pub fn to_char(self) -> char {
+        match self.color {
+            Color::White => self.kind.letter(),
+            Color::Black => self.kind.letter().to_ascii_lowercase(),
+        }
+    }
+
+    pub fn from_char(c: char) -> Option<Piece> {
+        let kind = PieceType::from_letter(c.to_ascii_uppercase())?;
+        let color = if c.is_ascii_uppercase() { Color::White } else { Color::Black };
+        Some(Piece::new(color, kind))
+    }
+
+    // Unicode chess symbol, ♔ to ♟
+    pub fn symbol(self) -> char {
+        let index = self.kind.code() as usize - 1;
+        match self.color {
+            Color::White => ['♙', '♘', '♗', '♖', '♕', '♔'][index],
+            Color::Black => ['♟', '♞', '♝', '♜', '♛', '♚'][index],
+        }
+    }
+}
+
+impl TryFrom<i8> for Piece {
+    type Error = String;
+
+    fn try_from(code: i8) -> Result<Piece, String> {
+        let kind = PieceType::from_code(code).ok_or(format!("Not a piece code: {}", code))?;
+        Ok(Piece::new(get_piece_color(code), kind))
+    }
+}
+
+impl From<Piece> for i8 {
+    fn from(piece: Piece) -> i8 {
+        match piece.color {
+            Color::White => piece.kind.code(),
+            Color::Black => -piece.kind.code(),
+        }
+    }
+}
+
+pub(crate) fn get_piece_color(piece: i8) -> Color {
     if piece > 0 {
         Color::White
     } else {
@@ -46,62 +239,22 @@ pub fn get_piece_value(piece: i8) -> i32 {
     }
 }
 
-fn get_knight_legals(
-    board: &[[i8; 8]; 8],
-    color: Color,
-    position: (usize, usize),
-) -> Vec<(usize, usize)> {
-    let mut legal_moves = Vec::new();
-    let (rank, file) = position;
-
-    let r_idx = rank as isize;
-    let f_idx = file as isize;
-
-    let moves = [
-        (r_idx - 2, f_idx - 1),
-        (r_idx - 2, f_idx + 1),
-        (r_idx - 1, f_idx - 2),
-        (r_idx - 1, f_idx + 2),
-        (r_idx + 1, f_idx - 2),
-        (r_idx + 1, f_idx + 2),
-        (r_idx + 2, f_idx - 1),
-        (r_idx + 2, f_idx + 1),
-    ];
-
-    for (r, f) in moves {
-        if r >= 0 && r < 8 && f >= 0 && f < 8 {
-            let u_r = r as usize;
-            let u_f = f as usize;
-
-            let piece = board[u_r][u_f];
-
-            if piece == E {
-                legal_moves.push((u_r, u_f));
-            } else {
-                let piece_color = get_piece_color(piece);
-
-                if piece_color != color {
-                    legal_moves.push((u_r, u_f));
-                }
-            }
-        }
-    }
-    legal_moves
+fn get_knight_legals(board: &[[i8; 8]; 8], color: Color, position: Square) -> Vec<Square> {
+    get_step_legals(board, color, &KNIGHT_TARGETS[position.rank()][position.file()])
 }
 
-fn is_on_board(r: isize, f: isize) -> bool {
-    r >= 0 && r < 8 && f >= 0 && f < 8
+// Knight and king moves: any listed square that isn't our own piece
+fn get_step_legals(board: &[[i8; 8]; 8], color: Color, targets: &SquareList) -> Vec<Square> {
+    targets
+        .as_slice()
+        .iter()
+        .copied()
+        .filter(|&square| board[square] == E || get_piece_color(board[square]) != color)
+        .collect()
 }
 
-fn get_pawn_legals(
-    board: &[[i8; 8]; 8],
-    color: Color,
-    position: (usize, usize),
-) -> Vec<(usize, usize)> {
+fn get_pawn_legals(board: &[[i8; 8]; 8], color: Color, position: Square) -> Vec<Square> {
     let mut legal_moves = Vec::new();
-    let (rank, file) = position;
-    let r_idx = rank as isize;
-    let f_idx = file as isize;
 
     // White moves up (decreasing index), black moves down (increasing index)
     // Assuming row 0 is rank 8, meaning black side
@@ -111,24 +264,18 @@ fn get_pawn_legals(
     };
 
     // One step forward
-    let r_next = r_idx + direction;
-    if is_on_board(r_next, f_idx) {
-        if board[r_next as usize][f_idx as usize] == E {
-            legal_moves.push((r_next as usize, f_idx as usize));
-
-            // Double step forward
-            let start_rank = match color {
-                Color::White => 6,
-                Color::Black => 1,
-            };
-
-            if rank == start_rank {
-                let r_double = r_idx + 2 * direction;
-                if is_on_board(r_double, f_idx) {
-                    if board[r_double as usize][f_idx as usize] == E {
-                        legal_moves.push((r_double as usize, f_idx as usize));
-                    }
-                }
+    if let Some(next) = position.offset(direction, 0).filter(|&next| board[next] == E) {
+        legal_moves.push(next);
+
+        // Double step forward
+        let start_rank = match color {
+            Color::White => 6,
+            Color::Black => 1,
+        };
+
+        if position.rank() == start_rank {
+            if let Some(double) = next.offset(direction, 0).filter(|&double| board[double] == E) {
+                legal_moves.push(double);
             }
         }
     }
@@ -136,15 +283,12 @@ fn get_pawn_legals(
     // Captures
     let capture_offsets = [-1, 1];
     for &offset in &capture_offsets {
-        let r_cap = r_idx + direction;
-        let f_cap = f_idx + offset;
-
-        if is_on_board(r_cap, f_cap) {
-            let target = board[r_cap as usize][f_cap as usize];
+        if let Some(capture) = position.offset(direction, offset) {
+            let target = board[capture];
             if target != E {
                 let target_color = get_piece_color(target);
                 if target_color != color {
-                    legal_moves.push((r_cap as usize, f_cap as usize));
+                    legal_moves.push(capture);
                 }
             }
         }
@@ -153,124 +297,46 @@ fn get_pawn_legals(
     legal_moves
 }
 
-fn get_sliding_legals(
-    board: &[[i8; 8]; 8],
-    color: Color,
-    position: (usize, usize),
-    directions: &[(isize, isize)],
-) -> Vec<(usize, usize)> {
+fn get_sliding_legals(board: &[[i8; 8]; 8], color: Color, position: Square, directions: &[usize]) -> Vec<Square> {
     let mut legal_moves = Vec::new();
-    let (rank, file) = position;
-
-    for &(dr, df) in directions {
-        let mut r = rank as isize + dr;
-        let mut f = file as isize + df;
 
-        while is_on_board(r, f) {
-            let u_r = r as usize;
-            let u_f = f as usize;
-            let piece = board[u_r][u_f];
+    for &direction in directions {
+        for &square in RAYS[position.rank()][position.file()][direction].as_slice() {
+            let piece = board[square];
 
             if piece == E {
-                legal_moves.push((u_r, u_f));
+                legal_moves.push(square);
             } else {
                 // Blocked
                 // Add capture move as well if blocked by opponent's stone
                 if get_piece_color(piece) != color {
-                    legal_moves.push((u_r, u_f));
+                    legal_moves.push(square);
                 }
                 break;
             }
-
-            r += dr;
-            f += df;
         }
     }
     legal_moves
 }
 
-fn get_bishop_legals(
-    board: &[[i8; 8]; 8],
-    color: Color,
-    position: (usize, usize),
-) -> Vec<(usize, usize)> {
-    let directions = [(-1, -1), (-1, 1), (1, -1), (1, 1)];
-    get_sliding_legals(board, color, position, &directions)
+fn get_bishop_legals(board: &[[i8; 8]; 8], color: Color, position: Square) -> Vec<Square> {
+    get_sliding_legals(board, color, position, &DIAGONAL)
 }
 
-fn get_rook_legals(
-    board: &[[i8; 8]; 8],
-    color: Color,
-    position: (usize, usize),
-) -> Vec<(usize, usize)> {
-    let directions = [(-1, 0), (1, 0), (0, -1), (0, 1)];
-    get_sliding_legals(board, color, position, &directions)
+fn get_rook_legals(board: &[[i8; 8]; 8], color: Color, position: Square) -> Vec<Square> {
+    get_sliding_legals(board, color, position, &ORTHOGONAL)
 }
 
-fn get_queen_legals(
-    board: &[[i8; 8]; 8],
-    color: Color,
-    position: (usize, usize),
-) -> Vec<(usize, usize)> {
-    let directions = [
-        (-1, -1),
-        (-1, 1),
-        (1, -1),
-        (1, 1),
-        (-1, 0),
-        (1, 0),
-        (0, -1),
-        (0, 1),
-    ];
-    get_sliding_legals(board, color, position, &directions)
+fn get_queen_legals(board: &[[i8; 8]; 8], color: Color, position: Square) -> Vec<Square> {
+    get_sliding_legals(board, color, position, &ALL_DIRECTIONS)
 }
 
-fn get_king_legals(
-    board: &[[i8; 8]; 8],
-    color: Color,
-    position: (usize, usize),
-) -> Vec<(usize, usize)> {
-    let mut legal_moves = Vec::new();
-    let (rank, file) = position;
-    let r_idx = rank as isize;
-    let f_idx = file as isize;
-
-    let moves = [
-        (r_idx - 1, f_idx - 1),
-        (r_idx - 1, f_idx),
-        (r_idx - 1, f_idx + 1),
-        (r_idx, f_idx - 1),
-        (r_idx, f_idx + 1),
-        (r_idx + 1, f_idx - 1),
-        (r_idx + 1, f_idx),
-        (r_idx + 1, f_idx + 1),
-    ];
-
-    for (r, f) in moves {
-        if is_on_board(r, f) {
-            let u_r = r as usize;
-            let u_f = f as usize;
-            let piece = board[u_r][u_f];
-
-            if piece == E || get_piece_color(piece) != color {
-                legal_moves.push((u_r, u_f));
-            }
-        }
-    }
-    legal_moves
+fn get_king_legals(board: &[[i8; 8]; 8], color: Color, position: Square) -> Vec<Square> {
+    get_step_legals(board, color, &KING_TARGETS[position.rank()][position.file()])
 }
 
-pub fn get_pseudo_legal_moves_for_piece(
-    board: &[[i8; 8]; 8],
-    color: Color,
-    position: (usize, usize),
-) -> Vec<(usize, usize)> {
-    let (rank, file) = position;
-
-    let r_idx = rank as usize;
-    let f_idx = file as usize;
-
-    let piece_type = board[r_idx][f_idx].abs();
+pub(crate) fn get_pseudo_legal_moves_for_piece(board: &[[i8; 8]; 8], color: Color, position: Square) -> Vec<Square> {
+    let piece_type = board[position].abs();
     match piece_type {
         WN => get_knight_legals(board, color, position),
         WP => get_pawn_legals(board, color, position),
@@ -282,25 +348,13 @@ pub fn get_pseudo_legal_moves_for_piece(
     }
 }
 
-pub fn get_all_pseudo_legal_moves(
-    board: &[[i8; 8]; 8],
-    color: Color,
-    ) -> Vec<((usize, usize), (usize, usize))> {
+// Moves of the pieces on `squares`, which all belong to `color`
+pub(crate) fn get_all_pseudo_legal_moves(board: &[[i8; 8]; 8], color: Color, squares: &[Square]) -> Vec<Move> {
     let mut all_legal_moves = Vec::new();
-    for rank in 0..8 {
-        for file in 0..8 {
-            let piece = board[rank][file];
-            if piece == E {
-                continue;
-            }
-            let piece_color = get_piece_color(board[rank][file]);
-            if piece_color != color {
-                continue;
-            }
-            let legal_moves = get_pseudo_legal_moves_for_piece(board, color, (rank, file));
-            for legal_move in legal_moves {
-                all_legal_moves.push(((rank, file), legal_move));
-            }
+    for &square in squares {
+        let legal_moves = get_pseudo_legal_moves_for_piece(board, color, square);
+        for legal_move in legal_moves {
+            all_legal_moves.push((square, legal_move));
         }
     }
     all_legal_moves
@@ -0,0 +1,145 @@
+// Piece-square tables: a bonus or penalty in centipawns for each piece on each
+// square, so pieces head for squares where they do something. Written from
+// White's side with row 0 as the eighth rank, the same layout as the board;
+// black pieces read them upside down. Kings and pawns have a second table for
+// the endgame, which the evaluation blends in as material comes off.
+
+use crate::chess::eval::Tapered;
+use crate::chess::pieces::{Square, E, WB, WK, WN, WP, WQ, WR};
+
+type Table = [[i32; 8]; 8];
+
+#[rustfmt::skip]
+const PAWN: Table = [
+    [  0,   0,   0,   0,   0,   0,   0,   0],
+    [ 50,  50,  50,  50,  50,  50,  50,  50],
+    [ 10,  10,  20,  30,  30,  20,  10,  10],
+    [  5,   5,  10,  25,  25,  10,   5,   5],
+    [  0,   0,   0,  20,  20,   0,   0,   0],
+    [  5,  -5, -10,   0,   0, -10,  -5,   5],
+    [  5,  10,  10, -20, -20,  10,  10,   5],
+    [  0,   0,   0,   0,   0,   0,   0,   0],
+];
+
+// Passers matter more than the center once the pieces are gone
+#[rustfmt::skip]
+const PAWN_ENDGAME: Table = [
+    [  0,   0,   0,   0,   0,   0,   0,   0],
+    [ 80,  80,  80,  80,  80,  80,  80,  80],
+    [ 50,  50,  50,  50,  50,  50,  50,  50],
+    [ 30,  30,  30,  30,  30,  30,  30,  30],
+    [ 15,  15,  15,  15,  15,  15,  15,  15],
+    [  5,   5,   5,   5,   5,   5,   5,   5],
+    [  0,   0,   0,   0,   0,   0,   0,   0],
+    [  0,   0,   0,   0,   0,   0,   0,   0],
+];
+
+#[rustfmt::skip]
+const KNIGHT: Table = [
+    [-50, -40, -30, -30, -30, -30, -40, -50],
+    [-40, -20,   0,   0,   0,   0, -20, -40],
+    [-30,   0,  10,  15,  15,  10,   0, -30],
+    [-30,   5,  15,  20,  20,  15,   5, -30],
+    [-30,   0,  15,  20,  20,  15,   0, -30],
+    [-30,   5,  10,  15,  15,  10,   5, -30],
+    [-40, -20,   0,   5,   5,   0, -20, -40],
+    [-50, -40, -30, -30, -30, -30, -40, -50],
+];
+
+#[rustfmt::skip]
+const BISHOP: Table = [
+    [-20, -10, -10, -10, -10, -10, -10, -20],
+    [-10,   0,   0,   0,   0,   0,   0, -10],
+    [-10,   0,   5,  10,  10,   5,   0, -10],
+    [-10,   5,   5,  10,  10,   5,   5, -10],
+    [-10,   0,  10,  10,  10,  10,   0, -10],
+    [-10,  10,  10,  10,  10,  10,  10, -10],
+    [-10,   5,   0,   0,   0,   0,   5, -10],
+    [-20, -10, -10, -10, -10, -10, -10, -20],
+];
+
+// The seventh rank, and the center files on the back rank to connect the rooks
+#[rustfmt::skip]
+const ROOK: Table = [
+    [  0,   0,   0,   0,   0,   0,   0,   0],
+    [  5,  10,  10,  10,  10,  10,  10,   5],
+    [ -5,   0,   0,   0,   0,   0,   0,  -5],
+    [ -5,   0,   0,   0,   0,   0,   0,  -5],
+    [ -5,   0,   0,   0,   0,   0,   0,  -5],
+    [ -5,   0,   0,   0,   0,   0,   0,  -5],
+    [ -5,   0,   0,   0,   0,   0,   0,  -5],
+    [  0,   0,   0,   5,   5,   0,   0,   0],
+];
+
+#[rustfmt::skip]
+const QUEEN: Table = [
+    [-20, -10, -10,  -5,  -5, -10, -10, -20],
+    [-10,   0,   0,   0,   0,   0,   0, -10],
+    [-10,   0,   5,   5,   5,   5,   0, -10],
+    [ -5,   0,   5,   5,   5,   5,   0,  -5],
+    [  0,   0,   5,   5,   5,   5,   0,  -5],
+    [-10,   5,   5,   5,   5,   5,   0, -10],
+    [-10,   0,   5,   0,   0,   0,   0, -10],
+    [-20, -10, -10,  -5,  -5, -10, -10, -20],
+];
+
+// Behind the pawns, castled, while there are pieces around to attack it
+#[rustfmt::skip]
+const KING_MIDDLEGAME: Table = [
+    [-30, -40, -40, -50, -50, -40, -40, -30],
+    [-30, -40, -40, -50, -50, -40, -40, -30],
+    [-30, -40, -40, -50, -50, -40, -40, -30],
+    [-30, -40, -40, -50, -50, -40, -40, -30],
+    [-20, -30, -30, -40, -40, -30, -30, -20],
+    [-10, -20, -20, -20, -20, -20, -20, -10],
+    [ 20,  20,   0,   0,   0,   0,  20,  20],
+    [ 20,  30,  10,   0,   0,  10,  30,  20],
+];
+
+// Central and active once the attackers are gone
+#[rustfmt::skip]
+const KING_ENDGAME: Table = [
+    [-50, -40, -30, -20, -20, -30, -40, -50],
+    [-30, -20, -10,   0,   0, -10, -20, -30],
+    [-30, -10,  20,  30,  30,  20, -10, -30],
+    [-30, -10,  30,  40,  40,  30, -10, -30],
+    [-30, -10,  30,  40,  40,  30, -10, -30],
+    [-30, -10,  20,  30,  30,  20, -10, -30],
+    [-30, -30,   0,   0,   0,   0, -30, -30],
+    [-50, -30, -30, -30, -30, -30, -30, -50],
+];
+
+fn table(piece: i8, endgame: bool) -> &'static Table {
+    match piece.abs() {
+        WP if endgame => &PAWN_ENDGAME,
+        WP => &PAWN,
+        WN => &KNIGHT,
+        WB => &BISHOP,
+        WR => &ROOK,
+        WQ => &QUEEN,
+        WK if endgame => &KING_ENDGAME,
+        _ => &KING_MIDDLEGAME,
+    }
+}
+
+// Table value of one piece on one square, from its own side's point of view
+fn square_value(piece: i8, square: Square, endgame: bool) -> i32 {
+    let square = if piece > 0 { square } else { square.flip() };
+    table(piece, endgame)[square]
+}
+
+// White-positive sums over the board, middlegame and endgame
+pub(crate) fn piece_square(board: &[[i8; 8]; 8]) -> Tapered {
+    let mut score = Tapered::default();
+    for square in Square::all() {
+        let piece = board[square];
+        if piece != E {
+            let sign = if piece > 0 { 1 } else { -1 };
+            score += Tapered {
+                mg: sign * square_value(piece, square, false),
+                eg: sign * square_value(piece, square, true),
+            };
+        }
+    }
+    score
+}
@@ -0,0 +1,132 @@
+// Piece counting for the evaluation: how many of each piece code stand on
+// the board. On x86_64 the 64 squares are compared 16 at a time with SSE2
+// (always there on that target) and the match masks popcounted. wasm does the
+// same with simd128 when built with the wasm-simd feature; browsers without
+// SIMD support can't load that build, so the default wasm build and every
+// other target use the scalar loop.
+//
+// Every square must hold a piece code from BK to WK. The scalar loop indexes
+// by code unchecked and the vector paths only look for those codes, so they
+// would disagree on anything else; boards from JS are checked in lib.rs.
+
+use crate::chess::timing::now_ms;
+
+// Indexed by piece code + 6, like the Zobrist keys: black king 0, white king 12
+pub type PieceCounts = [u8; 13];
+
+pub fn piece_counts(board: &[[i8; 8]; 8]) -> PieceCounts {
+    #[cfg(target_arch = "x86_64")]
+    {
+        // SAFETY: SSE2 is part of the x86_64 baseline
+        unsafe { piece_counts_sse2(board) }
+    }
+    #[cfg(all(target_arch = "wasm32", feature = "wasm-simd"))]
+    {
+        // SAFETY: the wasm-simd build requires a runtime with simd128
+        unsafe { piece_counts_simd128(board) }
+    }
+    #[cfg(not(any(target_arch = "x86_64", all(target_arch = "wasm32", feature = "wasm-simd"))))]
+    {
+        piece_counts_scalar(board)
+    }
+}
+
+pub fn piece_counts_scalar(board: &[[i8; 8]; 8]) -> PieceCounts {
+    let mut counts = [0; 13];
+    for &piece in board.as_flattened() {
+        counts[(piece + 6) as usize] += 1;
+    }
+    counts
+}
+
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "sse2")]
+unsafe fn piece_counts_sse2(board: &[[i8; 8]; 8]) -> PieceCounts {
+    use crate::chess::pieces::E;
+    use std::arch::x86_64::{__m128i, _mm_cmpeq_epi8, _mm_loadu_si128, _mm_movemask_epi8, _mm_set1_epi8};
+
+    let squares = board.as_flattened().as_ptr() as *const __m128i;
+    let chunks = [0, 1, 2, 3].map(|i| _mm_loadu_si128(squares.add(i)));
+
+    let mut counts = [0; 13];
+    let mut occupied = 0;
+    for piece in -6..=6 {
+        if piece == E {
+            continue;
+        }
+        let wanted = _mm_set1_epi8(piece);
+        let count: u32 = chunks
+            .iter()
+            .map(|&chunk| (_mm_movemask_epi8(_mm_cmpeq_epi8(chunk, wanted)) as u32).count_ones())
+            .sum();
+        counts[(piece + 6) as usize] = count as u8;
+        occupied += count;
+    }
+    counts[6] = (64 - occupied) as u8;
+    counts
+}
+
+#[cfg(all(target_arch = "wasm32", feature = "wasm-simd"))]
+#[target_feature(enable = "simd128")]
+unsafe fn piece_counts_simd128(board: &[[i8; 8]; 8]) -> PieceCounts {
+    use crate::chess::pieces::E;
+    use core::arch::wasm32::{i8x16_bitmask, i8x16_eq, i8x16_splat, v128, v128_load};
+
+    let squares = board.as_flattened().as_ptr() as *const v128;
+    let chunks = [0, 1, 2, 3].map(|i| v128_load(squares.add(i)));
+
+    let mut counts = [0; 13];
+    let mut occupied = 0;
+    for piece in -6..=6 {
+        if piece == E {
+            continue;
+        }
+        let wanted = i8x16_splat(piece);
+        let count: u32 = chunks.iter().map(|&chunk| i8x16_bitmask(i8x16_eq(chunk, wanted)).count_ones()).sum();
+        counts[(piece + 6) as usize] = count as u8;
+        occupied += count;
+    }
+    counts[6] = (64 - occupied) as u8;
+    counts
+}
+
+// Whether piece_counts has a vector path in this build
+pub const VECTORIZED: bool = cfg!(any(target_arch = "x86_64", all(target_arch = "wasm32", feature = "wasm-simd")));
+
+// Time piece_counts against the scalar loop over the bench positions.
+// Returns (piece_counts ms, scalar ms).
+pub fn bench_piece_counts(iterations: u32) -> (f64, f64) {
+    let boards: Vec<[[i8; 8]; 8]> = crate::chess::engine::BENCH_FENS
+        .iter()
+        .map(|fen| crate::chess::fen::parse_fen(fen).unwrap().board)
+        .collect();
+
+    let time = |count: fn(&[[i8; 8]; 8]) -> PieceCounts| {
+        let start = now_ms();
+        for _ in 0..iterations {
+            for board in &boards {
+                std::hint::black_box(count(std::hint::black_box(board)));
+            }
+        }
+        now_ms() - start
+    };
+    (time(piece_counts), time(piece_counts_scalar))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chess::engine::BENCH_FENS;
+    use crate::chess::fen::parse_fen;
+
+    #[test]
+    fn vector_and_scalar_counts_agree() {
+        let mut boards: Vec<[[i8; 8]; 8]> = BENCH_FENS.iter().map(|fen| parse_fen(fen).unwrap().board).collect();
+        // Empty, and every code in every chunk of 16 squares
+        boards.push([[0; 8]; 8]);
+        boards.push(std::array::from_fn(|rank| std::array::from_fn(|file| ((rank * 8 + file) % 13) as i8 - 6)));
+        for board in &boards {
+            assert_eq!(piece_counts(board), piece_counts_scalar(board));
+        }
+    }
+}
@@ -0,0 +1,140 @@
+use crate::chess::engine::{get_legal_moves, is_in_check, CastlingRights};
+use crate::chess::tables::zobrist_hash;
+use crate::chess::pieces::{Color, BB, BK, BN, E, WB, WK, WN};
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Termination {
+    Checkmate = 1,
+    Stalemate = 2,
+    Resignation = 3,
+    TimeForfeit = 4,
+    Repetition = 5,
+    FiftyMoveRule = 6,
+    InsufficientMaterial = 7,
+    Adjudication = 8,
+}
+
+impl Termination {
+    // Value for the PGN Termination tag
+    pub fn description(self) -> &'static str {
+        match self {
+            Termination::Checkmate => "checkmate",
+            Termination::Stalemate => "stalemate",
+            Termination::Resignation => "resignation",
+            Termination::TimeForfeit => "time forfeit",
+            Termination::Repetition => "threefold repetition",
+            Termination::FiftyMoveRule => "fifty-move rule",
+            Termination::InsufficientMaterial => "insufficient material",
+            Termination::Adjudication => "adjudication",
+        }
+    }
+
+    pub fn from_code(code: u8) -> Option<Termination> {
+        Some(match code {
+            1 => Termination::Checkmate,
+            2 => Termination::Stalemate,
+            3 => Termination::Resignation,
+            4 => Termination::TimeForfeit,
+            5 => Termination::Repetition,
+            6 => Termination::FiftyMoveRule,
+            7 => Termination::InsufficientMaterial,
+            8 => Termination::Adjudication,
+            _ => return None,
+        })
+    }
+
+    pub fn is_draw(self) -> bool {
+        matches!(
+            self,
+            Termination::Stalemate
+                | Termination::Repetition
+                | Termination::FiftyMoveRule
+                | Termination::InsufficientMaterial
+        )
+    }
+}
+
+// Neither side can ever mate: bare kings, a single minor piece, or bishops
+// that all stand on the same square color
+pub fn is_insufficient_material(board: &[[i8; 8]; 8]) -> bool {
+    let mut minors = 0;
+    let mut bishop_square_colors = Vec::new();
+    for (r, row) in board.iter().enumerate() {
+        for (f, &piece) in row.iter().enumerate() {
+            match piece {
+                E | WK | BK => {}
+                WN | BN => minors += 1,
+                WB | BB => {
+                    minors += 1;
+                    bishop_square_colors.push((r + f) % 2);
+                }
+                _ => return false,
+            }
+        }
+    }
+
+    minors <= 1
+        || (minors == bishop_square_colors.len()
+            && bishop_square_colors.iter().all(|&c| c == bishop_square_colors[0]))
+}
+
+// How the game ended in this position, if it did, by the rules alone.
+// history holds the Zobrist hashes of every earlier position of the game; the
+// current position counts as repeated when it appeared twice before with the
+// same side to move and castling rights. Resignation, time forfeit and
+// adjudication are decided by the caller, not the board.
+pub fn get_termination(
+    board: &[[i8; 8]; 8],
+    color: Color,
+    castling_rights: CastlingRights,
+    halfmove_clock: u32,
+    history: &[u64],
+) -> Option<Termination> {
+    if get_legal_moves(board, color, castling_rights).is_empty() {
+        return Some(if is_in_check(board, color) {
+            Termination::Checkmate
+        } else {
+            Termination::Stalemate
+        });
+    }
+    if is_insufficient_material(board) {
+        return Some(Termination::InsufficientMaterial);
+    }
+    if halfmove_clock >= 100 {
+        return Some(Termination::FiftyMoveRule);
+    }
+
+    let hash = zobrist_hash(board, color, castling_rights);
+    let repetitions = history.iter().filter(|&&previous| previous == hash).count();
+    if repetitions >= 2 {
+        return Some(Termination::Repetition);
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chess::fen::parse_square;
+    use crate::chess::game::Game;
+
+    fn play(game: &mut Game, moves: &str) {
+        for text in moves.split_whitespace() {
+            let move_ = (parse_square(&text[..2]).unwrap(), parse_square(&text[2..]).unwrap());
+            game.play(move_).unwrap();
+        }
+    }
+
+    #[test]
+    fn repetition_needs_the_same_castling_rights() {
+        // The kings walk out and back, so the board after 1. e4 e5 comes back,
+        // but without the castling rights it had then: two knight shuffles
+        // make it a repetition, not one
+        let mut game = Game::new();
+        play(&mut game, "e2e4 e7e5 e1e2 e8e7 e2e1 e7e8");
+        play(&mut game, "g1f3 g8f6 f3g1 f6g8");
+        assert_eq!(game.termination(), None);
+        play(&mut game, "g1f3 g8f6 f3g1 f6g8");
+        assert_eq!(game.termination(), Some(Termination::Repetition));
+    }
+}
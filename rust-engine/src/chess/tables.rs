@@ -0,0 +1,141 @@
+// Lookup tables built by const fn. They are statics evaluated at compile time,
+// so they sit in the binary (and the wasm data section) ready to use: no
+// startup work and no lazy initialization.
+
+use crate::chess::engine::CastlingRights;
+use crate::chess::pieces::{Color, Square};
+
+// Up to eight squares, enough for knight and king targets and for any ray
+#[derive(Clone, Copy)]
+pub struct SquareList {
+    squares: [Square; 8],
+    len: usize,
+}
+
+impl SquareList {
+    const EMPTY: SquareList = SquareList { squares: [Square::new(0, 0); 8], len: 0 };
+
+    pub fn as_slice(&self) -> &[Square] {
+        &self.squares[..self.len]
+    }
+}
+
+// Ray directions; the first four are orthogonal, the last four diagonal
+pub const DIRECTIONS: [(isize, isize); 8] = [(-1, 0), (1, 0), (0, -1), (0, 1), (-1, -1), (-1, 1), (1, -1), (1, 1)];
+pub const ORTHOGONAL: [usize; 4] = [0, 1, 2, 3];
+pub const DIAGONAL: [usize; 4] = [4, 5, 6, 7];
+pub const ALL_DIRECTIONS: [usize; 8] = [0, 1, 2, 3, 4, 5, 6, 7];
+
+const KNIGHT_OFFSETS: [(isize, isize); 8] = [(-2, -1), (-2, 1), (-1, -2), (-1, 2), (1, -2), (1, 2), (2, -1), (2, 1)];
+const KING_OFFSETS: [(isize, isize); 8] = [(-1, -1), (-1, 0), (-1, 1), (0, -1), (0, 1), (1, -1), (1, 0), (1, 1)];
+
+pub static KNIGHT_TARGETS: [[SquareList; 8]; 8] = step_targets(&KNIGHT_OFFSETS);
+pub static KING_TARGETS: [[SquareList; 8]; 8] = step_targets(&KING_OFFSETS);
+
+// RAYS[rank][file][direction]: squares from next to the origin out to the edge
+pub static RAYS: [[[SquareList; 8]; 8]; 8] = rays();
+
+const fn step_targets(offsets: &[(isize, isize); 8]) -> [[SquareList; 8]; 8] {
+    let mut table = [[SquareList::EMPTY; 8]; 8];
+    let mut square = 0;
+    while square < 64 {
+        let (rank, file) = (square / 8, square % 8);
+        let mut list = SquareList::EMPTY;
+        let mut i = 0;
+        while i < 8 {
+            let r = rank as isize + offsets[i].0;
+            let f = file as isize + offsets[i].1;
+            if r >= 0 && r < 8 && f >= 0 && f < 8 {
+                list.squares[list.len] = Square::new(r as usize, f as usize);
+                list.len += 1;
+            }
+            i += 1;
+        }
+        table[rank][file] = list;
+        square += 1;
+    }
+    table
+}
+
+const fn rays() -> [[[SquareList; 8]; 8]; 8] {
+    let mut table = [[[SquareList::EMPTY; 8]; 8]; 8];
+    let mut square = 0;
+    while square < 64 {
+        let (rank, file) = (square / 8, square % 8);
+        let mut direction = 0;
+        while direction < 8 {
+            let (dr, df) = DIRECTIONS[direction];
+            let mut list = SquareList::EMPTY;
+            let mut r = rank as isize + dr;
+            let mut f = file as isize + df;
+            while r >= 0 && r < 8 && f >= 0 && f < 8 {
+                list.squares[list.len] = Square::new(r as usize, f as usize);
+                list.len += 1;
+                r += dr;
+                f += df;
+            }
+            table[rank][file][direction] = list;
+            direction += 1;
+        }
+        square += 1;
+    }
+    table
+}
+
+// Zobrist keys from a fixed splitmix64 stream, so hashes are the same on
+// every build and target
+pub struct ZobristKeys {
+    // Indexed by piece + 6 (so black king is 0, white king 12; 6 is unused)
+    pub pieces: [[[u64; 8]; 8]; 13],
+    pub black_to_move: u64,
+    pub castling: [u64; 16],
+}
+
+pub static ZOBRIST: ZobristKeys = zobrist_keys();
+
+const fn splitmix64(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+const fn zobrist_keys() -> ZobristKeys {
+    let mut state = 0x5EED;
+    let mut keys = ZobristKeys { pieces: [[[0; 8]; 8]; 13], black_to_move: 0, castling: [0; 16] };
+    let mut piece = 0;
+    while piece < 13 {
+        let mut square = 0;
+        while square < 64 {
+            // Empty squares keep a zero key so they drop out of the hash
+            if piece != 6 {
+                keys.pieces[piece][square / 8][square % 8] = splitmix64(&mut state);
+            }
+            square += 1;
+        }
+        piece += 1;
+    }
+    keys.black_to_move = splitmix64(&mut state);
+    let mut rights = 1;
+    while rights < 16 {
+        keys.castling[rights] = splitmix64(&mut state);
+        rights += 1;
+    }
+    keys
+}
+
+// Every square must hold a piece code from BK to WK; boards from JS are
+// checked for that in lib.rs before they get here
+pub fn zobrist_hash(board: &[[i8; 8]; 8], color: Color, castling_rights: CastlingRights) -> u64 {
+    let mut hash = 0;
+    for (r, row) in board.iter().enumerate() {
+        for (f, &piece) in row.iter().enumerate() {
+            hash ^= ZOBRIST.pieces[(piece + 6) as usize][r][f];
+        }
+    }
+    if color == Color::Black {
+        hash ^= ZOBRIST.black_to_move;
+    }
+    hash ^ ZOBRIST.castling[castling_rights.bits() as usize]
+}
@@ -0,0 +1,26 @@
+// Monotonic milliseconds from an arbitrary start, for timing searches and
+// deadlines; only differences mean anything. std::time::Instant panics on
+// wasm32-unknown-unknown, so the browser build asks JS's performance.now()
+// instead (there in pages, workers and node alike).
+#[cfg(target_arch = "wasm32")]
+pub fn now_ms() -> f64 {
+    use wasm_bindgen::prelude::*;
+
+    #[wasm_bindgen]
+    extern "C" {
+        #[wasm_bindgen(js_namespace = performance, js_name = now)]
+        fn performance_now() -> f64;
+    }
+    performance_now()
+}
+
+// Measured from the first call in the process, so a wall clock being set
+// back can't make a deadline pass early or never
+#[cfg(not(target_arch = "wasm32"))]
+pub fn now_ms() -> f64 {
+    use std::sync::OnceLock;
+    use std::time::Instant;
+
+    static START: OnceLock<Instant> = OnceLock::new();
+    START.get_or_init(Instant::now).elapsed().as_secs_f64() * 1000.0
+}
@@ -0,0 +1,166 @@
+// Defaults for the CLI, read from a TOML file so experiments don't need long
+// flag lists. Flags given on the command line still win. Example:
+//
+//   [search]
+//   preset = "rapid"        # bullet, blitz, rapid or analysis; depth and pruning margins
+//   depth = 5
+//   pruning = true
+//   move_ordering = true
+//
+//   [watch]
+//   delay_ms = 500
+//   max_plies = 200
+//   color = true
+//
+//   [match]
+//   side = "black"
+//   time_ms = 180000
+//   increment_ms = 2000
+//   delay_ms = 3000         # US delay; or bronstein_ms for Bronstein delay
+//   byo_yomi_ms = 30000     # byo-yomi periods after the main time
+//   periods = 5
+
+use crate::chess::clock::{Delay, TimeControl};
+use crate::chess::engine::SearchPreset;
+use crate::chess::pieces::Color;
+
+// Looked for in the working directory when --config isn't given
+pub const DEFAULT_CONFIG_PATH: &str = "chess_cli.toml";
+
+pub struct CliConfig {
+    pub preset: SearchPreset,
+    // The preset's depth unless set on its own
+    pub depth: i32,
+    pub use_pruning: bool,
+    pub use_move_ordering: bool,
+    pub delay_ms: u64,
+    pub max_plies: Option<usize>,
+    pub use_color: bool,
+    pub engine_side: Color,
+    pub time_control: TimeControl,
+}
+
+impl Default for CliConfig {
+    fn default() -> Self {
+        CliConfig {
+            preset: SearchPreset::default(),
+            depth: SearchPreset::default().depth,
+            use_pruning: true,
+            use_move_ordering: true,
+            delay_ms: 0,
+            max_plies: None,
+            use_color: false,
+            engine_side: Color::White,
+            time_control: TimeControl::new(60_000, 0),
+        }
+    }
+}
+
+impl CliConfig {
+    // Load `path`, or DEFAULT_CONFIG_PATH if it exists, or fall back to the defaults
+    pub fn load(path: Option<&str>) -> Result<CliConfig, String> {
+        let text = match path {
+            Some(path) => std::fs::read_to_string(path).map_err(|err| format!("Cannot read {}: {}", path, err))?,
+            None => match std::fs::read_to_string(DEFAULT_CONFIG_PATH) {
+                Ok(text) => text,
+                Err(_) => return Ok(CliConfig::default()),
+            },
+        };
+        CliConfig::parse(&text)
+    }
+
+    pub fn parse(text: &str) -> Result<CliConfig, String> {
+        let table: toml::Table = text.parse().map_err(|err| format!("Bad config: {}", err))?;
+        let mut config = CliConfig::default();
+
+        for (section, values) in &table {
+            let Some(values) = values.as_table() else {
+                eprintln!("Ignoring config key '{}' outside a section", section);
+                continue;
+            };
+
+            // The preset goes first so explicit settings in the same section override it
+            if section == "search" {
+                if let Some(name) = values.get("preset") {
+                    let name = name.as_str().ok_or("search.preset must be a string")?;
+                    config.preset = SearchPreset::from_name(name).ok_or_else(|| format!("Unknown preset '{}'", name))?;
+                    config.depth = config.preset.depth;
+                }
+            }
+
+            for (key, value) in values {
+                let name = format!("{}.{}", section, key);
+                let int = || value.as_integer().filter(|&n| n >= 0).ok_or(format!("{} must be a non-negative integer", name));
+                let bool = || value.as_bool().ok_or(format!("{} must be true or false", name));
+                match (section.as_str(), key.as_str()) {
+                    ("search", "preset") => {}
+                    ("search", "depth") => {
+                        config.depth = value
+                            .as_integer()
+                            .filter(|&n| n >= 1)
+                            .ok_or(format!("{} must be a positive integer", name))? as i32
+                    }
+                    ("search", "pruning") => config.use_pruning = bool()?,
+                    ("search", "move_ordering") => config.use_move_ordering = bool()?,
+                    ("watch", "delay_ms") => config.delay_ms = int()? as u64,
+                    ("watch", "max_plies") => config.max_plies = Some(int()? as usize),
+                    ("watch", "color") => config.use_color = bool()?,
+                    ("match", "side") => {
+                        config.engine_side = match value.as_str() {
+                            Some("white") => Color::White,
+                            Some("black") => Color::Black,
+                            _ => return Err(format!("{} must be \"white\" or \"black\"", name)),
+                        }
+                    }
+                    ("match", "time_ms") => config.time_control.initial_ms = int()? as u64,
+                    ("match", "increment_ms") => config.time_control.increment_ms = int()? as u64,
+                    ("match", "delay_ms") => config.time_control.delay = Delay::Us(int()? as u64),
+                    ("match", "bronstein_ms") => config.time_control.delay = Delay::Bronstein(int()? as u64),
+                    ("match", "byo_yomi_ms") => config.time_control.byo_yomi_ms = int()? as u64,
+                    ("match", "periods") => config.time_control.periods = int()? as u32,
+                    _ => eprintln!("Ignoring unknown config setting '{}'", name),
+                }
+            }
+        }
+        Ok(config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn explicit_depth_beats_the_preset_in_any_order() {
+        for text in ["[search]\ndepth = 6\npreset = \"bullet\"", "[search]\npreset = \"bullet\"\ndepth = 6"] {
+            let config = CliConfig::parse(text).unwrap();
+            assert_eq!(config.preset, SearchPreset::BULLET);
+            assert_eq!(config.depth, 6);
+        }
+
+        let config = CliConfig::parse("[search]\npreset = \"Analysis\"").unwrap();
+        assert_eq!(config.preset, SearchPreset::ANALYSIS);
+        assert_eq!(config.depth, SearchPreset::ANALYSIS.depth);
+    }
+
+    #[test]
+    fn bad_values_are_errors() {
+        assert!(CliConfig::parse("[search]\npreset = \"classical\"").is_err());
+        assert!(CliConfig::parse("[search]\npreset = 3").is_err());
+        assert!(CliConfig::parse("[search]\ndepth = 0").is_err());
+        assert!(CliConfig::parse("[match]\nside = \"red\"").is_err());
+        assert!(CliConfig::parse("[watch]\ndelay_ms = -5").is_err());
+        assert!(CliConfig::parse("[search").is_err());
+    }
+
+    #[test]
+    fn match_settings_build_the_time_control() {
+        let text = "[match]\nside = \"black\"\ntime_ms = 180000\nbronstein_ms = 3000\nbyo_yomi_ms = 30000\nperiods = 5";
+        let config = CliConfig::parse(text).unwrap();
+        assert_eq!(config.engine_side, Color::Black);
+        assert_eq!(config.time_control.initial_ms, 180_000);
+        assert_eq!(config.time_control.delay, Delay::Bronstein(3_000));
+        assert_eq!((config.time_control.byo_yomi_ms, config.time_control.periods), (30_000, 5));
+        assert_eq!(config.depth, SearchPreset::default().depth);
+    }
+}
@@ -2,6 +2,13 @@ use wasm_bindgen::prelude::*;
 
 pub mod chess;
 mod math;
+mod protocol;
+pub mod prelude;
+
+// wasm-threads builds: JS awaits initThreadPool(navigator.hardwareConcurrency)
+// once, on a cross-origin isolated page, before searching in parallel
+#[cfg(all(target_arch = "wasm32", feature = "wasm-threads"))]
+pub use wasm_bindgen_rayon::init_thread_pool;
 
 #[wasm_bindgen]
 pub fn calculate_fib(n: u32) -> u32 {
@@ -9,71 +16,1008 @@ pub fn calculate_fib(n: u32) -> u32 {
 }
 
 // Have to flatten the arrays for wasm
-// Pass on flat arrays back and parse them in js
-fn convert_flat_to_2d(flat_board: &[i8]) -> [[i8; 8]; 8] {
+// Pass on flat arrays back and parse them in js. Boards are checked here, on
+// the way in, so the engine can index its tables by piece code unchecked.
+fn board_from_js(flat_board: &[i8]) -> Result<[[i8; 8]; 8], JsValue> {
+    if flat_board.len() != 64 {
+        return Err(JsValue::from_str(&format!("A board needs 64 squares, got {}", flat_board.len())));
+    }
     let mut board_2d = [[0i8; 8]; 8];
-    for i in 0..8 {
-        for j in 0..8 {
-            board_2d[i][j] = flat_board[i * 8 + j];
+    for (index, &piece) in flat_board.iter().enumerate() {
+        if !(chess::pieces::BK..=chess::pieces::WK).contains(&piece) {
+            return Err(JsValue::from_str(&format!("Bad piece code {} on square {}", piece, index)));
         }
+        board_2d[index / 8][index % 8] = piece;
+    }
+    Ok(board_2d)
+}
+
+// Squares cross to JS as rank and file, row 0 being the eighth rank
+fn move_coords((from, to): chess::pieces::Move) -> [usize; 4] {
+    [from.rank(), from.file(), to.rank(), to.file()]
+}
+
+// A move from JS; None if either square is off the board
+fn coords_move(from_rank: usize, from_file: usize, to_rank: usize, to_file: usize) -> Option<chess::pieces::Move> {
+    let square = |rank, file| chess::pieces::Square::try_new(rank as isize, file as isize);
+    Some((square(from_rank, from_file)?, square(to_rank, to_file)?))
+}
+
+// [from_rank, from_file, to_rank, to_file, eval_count], or [] with no move
+fn flatten_best_move(best_move: Option<(chess::pieces::Square, chess::pieces::Square, u32)>) -> Vec<usize> {
+    match best_move {
+        Some((from, to, eval_count)) => {
+            let mut flat = move_coords((from, to)).to_vec();
+            flat.push(eval_count as usize);
+            flat
+        }
+        None => vec![],
+    }
+}
+
+// Rights from JS: the four CASTLE_* bits, anything else ignored
+fn castling(bits: u8) -> chess::engine::CastlingRights {
+    chess::engine::CastlingRights::from_bits_truncate(bits)
+}
+
+#[wasm_bindgen]
+pub fn get_all_legal_moves(board: &[i8], color_int: i32, castling_rights: u8) -> Result<Vec<usize>, JsValue> {
+    let color = chess::pieces::Color::from_int(color_int);
+
+    let board_2d = board_from_js(board)?;
+
+    let moves = chess::engine::get_legal_moves(&board_2d, color, castling(castling_rights));
+
+    let mut flat = Vec::new();
+    for move_ in moves {
+        flat.extend(move_coords(move_));
     }
-    board_2d
+    Ok(flat)
+}
+
+// All legal moves as 64 destination bitmasks (a BigUint64Array in JS), one per
+// origin square rank * 8 + file; bit rank * 8 + file is set for each legal target
+#[wasm_bindgen]
+pub fn get_legal_move_masks(board: &[i8], color_int: i32, castling_rights: u8) -> Result<Vec<u64>, JsValue> {
+    let color = chess::pieces::Color::from_int(color_int);
+    let board_2d = board_from_js(board)?;
+    Ok(chess::engine::legal_move_masks(&board_2d, color, castling(castling_rights)).to_vec())
 }
 
 #[wasm_bindgen]
-pub fn get_all_legal_moves(board: &[i8], color_int: i32, castling_rights: u8) -> Vec<usize> {
-    let color = if color_int == 0 {
-        chess::pieces::Color::White
+pub fn get_best_move(board: &[i8], color_int: i32, depth: i32, castling_rights: u8, use_pruning: bool, use_move_ordering: bool) -> Result<Vec<usize>, JsValue> {
+    let color = chess::pieces::Color::from_int(color_int);
+
+    let board_2d = board_from_js(board)?;
+
+    let best_move = chess::engine::get_best_move(&board_2d, color, depth, castling(castling_rights), use_pruning, use_move_ordering, None);
+    Ok(flatten_best_move(best_move))
+}
+
+// Same as get_best_move, but the engine won't play back into previous_board
+// (the position before its own last move) unless it has no other choice.
+// Pass an empty previous_board to disable the check.
+#[wasm_bindgen]
+pub fn get_best_move_casual(
+    board: &[i8],
+    color_int: i32,
+    depth: i32,
+    castling_rights: u8,
+    use_pruning: bool,
+    use_move_ordering: bool,
+    previous_board: &[i8],
+) -> Result<Vec<usize>, JsValue> {
+    let color = chess::pieces::Color::from_int(color_int);
+
+    let board_2d = board_from_js(board)?;
+    let previous_2d = if previous_board.is_empty() {
+        None
     } else {
-        chess::pieces::Color::Black
+        Some(board_from_js(previous_board)?)
     };
 
-    let board_2d = convert_flat_to_2d(&board);
+    let best_move = chess::engine::get_best_move(
+        &board_2d,
+        color,
+        depth,
+        castling(castling_rights),
+        use_pruning,
+        use_move_ordering,
+        previous_2d.as_ref(),
+    );
+    Ok(flatten_best_move(best_move))
+}
 
-    let moves = chess::engine::get_legal_moves(&board_2d, color, castling_rights);
+#[wasm_bindgen]
+pub fn is_in_check(board: &[i8], color_int: i32) -> Result<bool, JsValue> {
+    let color = chess::pieces::Color::from_int(color_int);
+    let board_2d = board_from_js(board)?;
+    Ok(chess::engine::is_in_check(&board_2d, color))
+}
 
+// Evaluation bonus in centipawns for holding both bishops (halved with all
+// sixteen pawns on the board); 60 by default, 0 turns it off
+#[wasm_bindgen]
+pub fn set_bishop_pair_bonus(centipawns: i32) {
+    chess::eval::set_bishop_pair_bonus(centipawns);
+}
+
+// How far the game has gone by the pieces left: [phase, name], phase from 256
+// (every piece on the board) down to 0, name 0 opening, 1 middlegame, 2 endgame
+#[wasm_bindgen]
+pub fn get_game_phase(board: &[i8]) -> Result<Vec<i32>, JsValue> {
+    let board_2d = board_from_js(board)?;
+    Ok(vec![chess::eval::get_game_phase(&board_2d), chess::eval::classify_game_phase(&board_2d) as i32])
+}
+
+// Fix the engine's randomness (tie-breaks between equal moves, hint noise) so
+// a game can be replayed exactly, here or with `chess_cli watch --seed`
+#[wasm_bindgen]
+pub fn set_random_seed(seed: u32) {
+    chess::engine::set_seed(seed as u64);
+}
+
+#[wasm_bindgen]
+pub fn get_hint(board: &[i8], color_int: i32, castling_rights: u8, level: u8) -> Result<Vec<usize>, JsValue> {
+    let color = chess::pieces::Color::from_int(color_int);
+    let board_2d = board_from_js(board)?;
+
+    Ok(chess::engine::get_hint(&board_2d, color, castling(castling_rights), level)
+        .map_or(vec![], |move_| move_coords(move_).to_vec()))
+}
+
+// Total size of the wasm linear memory in bytes. This is everything the module
+// has reserved, free space included, not what the engine currently has
+// allocated; it only grows, never shrinks. Native builds have no such memory
+// and report 0. A number rather than usize, since a full 4 GiB memory doesn't
+// fit in wasm32's usize.
+#[wasm_bindgen]
+pub fn get_memory_usage() -> f64 {
+    #[cfg(target_arch = "wasm32")]
+    {
+        core::arch::wasm32::memory_size(0) as f64 * 65536.0
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        0.0
+    }
+}
+
+// Streaming PGN reader for JS: push file chunks in with feed(), get back the
+// games completed so far as PGN strings, then call finish() for the last one.
+#[wasm_bindgen]
+pub struct PgnStream {
+    reader: chess::pgn::PgnReader,
+}
+
+impl Default for PgnStream {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[wasm_bindgen]
+impl PgnStream {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> PgnStream {
+        PgnStream {
+            reader: chess::pgn::PgnReader::new(),
+        }
+    }
+
+    pub fn feed(&mut self, chunk: &str) -> Vec<String> {
+        self.reader.feed(chunk).iter().map(|game| game.to_pgn()).collect()
+    }
+
+    pub fn finish(self) -> Option<String> {
+        self.reader.finish().map(|game| game.to_pgn())
+    }
+}
+
+#[wasm_bindgen]
+pub fn canonicalize_fen(fen: &str) -> Result<String, JsValue> {
+    chess::fen::canonical_fen(fen).map_err(|err| JsValue::from_str(&err))
+}
+
+#[wasm_bindgen]
+pub fn get_position_key(fen: &str) -> Result<u64, JsValue> {
+    chess::fen::position_key(fen).map_err(|err| JsValue::from_str(&err))
+}
+
+// Threat data for a move that was just played by color_int (usually the engine).
+// Returns [n, r1, f1, ..., rn, fn] for the n newly attacked pieces, followed by
+// [from_rank, from_file, to_rank, to_file] if the move sets up a mate threat.
+// Just [0] for a move that isn't legal here.
+#[wasm_bindgen]
+pub fn get_move_threats(board: &[i8], color_int: i32, castling_rights: u8, move_: &[usize]) -> Result<Vec<usize>, JsValue> {
+    let color = chess::pieces::Color::from_int(color_int);
+    let board_2d = board_from_js(board)?;
+    if move_.len() != 4 {
+        return Ok(vec![0]);
+    }
+    let castling_rights = castling(castling_rights);
+    // Anything but a legal move (off the board included) has no threats to show
+    let Some(move_) = coords_move(move_[0], move_[1], move_[2], move_[3]) else {
+        return Ok(vec![0]);
+    };
+    if !chess::engine::get_legal_moves(&board_2d, color, castling_rights).contains(&move_) {
+        return Ok(vec![0]);
+    }
+    let threats = chess::engine::get_move_threats(&board_2d, color, castling_rights, move_);
+
+    let mut flat = vec![threats.newly_attacked.len()];
+    for square in threats.newly_attacked {
+        flat.push(square.rank());
+        flat.push(square.file());
+    }
+    if let Some(threat) = threats.mate_threat {
+        flat.extend(move_coords(threat));
+    }
+    Ok(flat)
+}
+
+// [from_rank, from_file, to_rank, to_file] of the best move, followed by the
+// same four numbers for the best reply when there is one
+#[wasm_bindgen]
+pub fn get_plans(board: &[i8], color_int: i32, depth: i32, castling_rights: u8) -> Result<Vec<usize>, JsValue> {
+    let color = chess::pieces::Color::from_int(color_int);
+    let board_2d = board_from_js(board)?;
+
+    let (best, reply) = chess::engine::get_plans(&board_2d, color, depth, castling(castling_rights));
     let mut flat = Vec::new();
-    for ((from_rank, from_file), (to_rank, to_file)) in moves {
-        flat.push(from_rank);
-        flat.push(from_file);
-        flat.push(to_rank);
-        flat.push(to_file);
+    for move_ in best.into_iter().chain(reply) {
+        flat.extend(move_coords(move_));
+    }
+    Ok(flat)
+}
+
+// Evaluation after every ply of a game played from the initial position.
+// moves is the usual flat [from_rank, from_file, to_rank, to_file, ...] list;
+// scores are in pawns, white-positive, and mates come back as +-10000. Stops
+// at a move that is off the board, as at an illegal one.
+#[wasm_bindgen]
+pub fn eval_series(moves: &[usize], depth: i32) -> Vec<f32> {
+    let start = chess::fen::parse_fen(chess::fen::START_FEN).unwrap();
+    let moves: Vec<_> = moves
+        .chunks_exact(4)
+        .map_while(|m| coords_move(m[0], m[1], m[2], m[3]))
+        .collect();
+
+    scores_to_pawns(chess::engine::eval_series(&start, &moves, depth))
+}
+
+fn scores_to_pawns(scores: Vec<i32>) -> Vec<f32> {
+    scores
+        .into_iter()
+        .map(|score| {
+            if chess::engine::is_mate_score(score) {
+                score.signum() as f32 * chess::engine::MATE_SCORE as f32
+            } else {
+                score as f32 / chess::eval::PAWN_VALUE as f32
+            }
+        })
+        .collect()
+}
+
+// Fixed bench suite for the native vs wasm comparison (see bench/compare.sh).
+// Returns [leaf nodes, elapsed ms, nodes per second].
+#[wasm_bindgen]
+pub fn run_bench(depth: i32) -> Vec<f64> {
+    let (nodes, elapsed) = chess::engine::bench(depth);
+    vec![nodes as f64, elapsed, nodes as f64 * 1000.0 / elapsed.max(1.0)]
+}
+
+// piece_counts (simd128 in the wasm-simd build) against its scalar loop, for
+// measuring what the SIMD build buys. Returns [vectorized build 0/1,
+// piece_counts ms, scalar ms, speedup].
+#[wasm_bindgen]
+pub fn run_simd_bench(iterations: u32) -> Vec<f64> {
+    let (fast, scalar) = chess::simd::bench_piece_counts(iterations);
+    vec![chess::simd::VECTORIZED as u8 as f64, fast, scalar, scalar / fast.max(1e-9)]
+}
+
+// Short fixed benchmark for picking default settings per device.
+// Returns [recommended depth, elapsed ms, nodes per second] for searches that
+// should take about target_ms.
+#[wasm_bindgen]
+pub fn calibrate_depth(target_ms: f64) -> Vec<f64> {
+    let (depth, elapsed, nps) = chess::engine::calibrate(target_ms);
+    vec![depth as f64, elapsed, nps as f64]
+}
+
+// Legal moves for several positions in one call. boards holds the 64-square
+// boards back to back, with one color and one rights byte per board.
+// Output per position: [move_count, from_rank, from_file, to_rank, to_file, ...]
+#[wasm_bindgen]
+pub fn get_all_legal_moves_batch(boards: &[i8], colors: &[i32], castling_rights: &[u8]) -> Result<Vec<usize>, JsValue> {
+    let mut flat = Vec::new();
+    for ((board, &color_int), &rights) in boards.chunks_exact(64).zip(colors).zip(castling_rights) {
+        let color = chess::pieces::Color::from_int(color_int);
+        let board_2d = board_from_js(board)?;
+        let moves = chess::engine::get_legal_moves(&board_2d, color, castling(rights));
+
+        flat.push(moves.len());
+        for move_ in moves {
+            flat.extend(move_coords(move_));
+        }
+    }
+    Ok(flat)
+}
+
+// White's expected score (0.0 - 1.0) for the position after a depth-limited search
+#[wasm_bindgen]
+pub fn win_probability(board: &[i8], color_int: i32, depth: i32, castling_rights: u8) -> Result<f64, JsValue> {
+    let color = chess::pieces::Color::from_int(color_int);
+    let mut board_2d = board_from_js(board)?;
+    let mut eval_count = 0;
+    let score = chess::engine::minimax(
+        &mut board_2d,
+        color,
+        depth,
+        -50000,
+        50000,
+        castling(castling_rights),
+        true,
+        true,
+        &mut eval_count,
+    );
+    Ok(chess::engine::win_probability(score, &board_2d))
+}
+
+// get_best_move with the depth and pruning margins of a named preset:
+// "bullet", "blitz", "rapid" or "analysis"
+#[wasm_bindgen]
+pub fn get_best_move_preset(board: &[i8], color_int: i32, castling_rights: u8, preset: &str) -> Result<Vec<usize>, JsValue> {
+    let preset = chess::engine::SearchPreset::from_name(preset)
+        .ok_or_else(|| JsValue::from_str(&format!("Unknown preset '{}'", preset)))?;
+    chess::engine::with_preset(preset, || get_best_move(board, color_int, preset.depth, castling_rights, true, true))
+}
+
+// Stateful engine object for JS. Methods take &self and report overlapping
+// calls as a "SearchInProgress" error instead of corrupting the position.
+#[wasm_bindgen]
+pub struct Engine {
+    handle: chess::handle::EngineHandle,
+}
+
+impl Default for Engine {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[wasm_bindgen]
+impl Engine {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Engine {
+        Engine {
+            handle: chess::handle::EngineHandle::new(),
+        }
+    }
+
+    pub fn set_position(&self, board: &[i8], color_int: i32, castling_rights: u8) -> Result<(), JsValue> {
+        let color = chess::pieces::Color::from_int(color_int);
+        let castling_rights = chess::engine::CastlingRights::from_bits(castling_rights)
+            .ok_or_else(|| JsValue::from_str(&format!("Bad castling rights {}", castling_rights)))?;
+        self.handle
+            .set_position(chess::handle::EngineState {
+                board: board_from_js(board)?,
+                color,
+                castling_rights,
+            })
+            .map_err(|err| JsValue::from_str(&err.to_string()))
+    }
+
+    // Flat 64-square board of the current position
+    pub fn board(&self) -> Result<Vec<i8>, JsValue> {
+        let state = self.handle.state().map_err(|err| JsValue::from_str(&err.to_string()))?;
+        Ok(state.board.iter().flatten().copied().collect())
+    }
+
+    // Returns the move's class code, as play_move on Game does
+    pub fn play_move(&self, from_rank: usize, from_file: usize, to_rank: usize, to_file: usize) -> Result<u8, JsValue> {
+        let Some(move_) = coords_move(from_rank, from_file, to_rank, to_file) else {
+            return Ok(chess::engine::MoveClass::Illegal as u8);
+        };
+        let state = self.handle.state().map_err(|err| JsValue::from_str(&err.to_string()))?;
+        let class = chess::engine::classify_move(&state.board, state.color, state.castling_rights, move_);
+        if class != chess::engine::MoveClass::Illegal {
+            self.handle
+                .play_move(move_)
+                .map_err(|err| JsValue::from_str(&err.to_string()))?;
+        }
+        Ok(class as u8)
+    }
+
+    pub fn best_move(&self, depth: i32, use_pruning: bool, use_move_ordering: bool) -> Result<Vec<usize>, JsValue> {
+        let best_move = self
+            .handle
+            .best_move(depth, use_pruning, use_move_ordering)
+            .map_err(|err| JsValue::from_str(&err.to_string()))?;
+        Ok(flatten_best_move(best_move))
+    }
+}
+
+// [from_rank, from_file, to_rank, to_file, eval_count, depth] of a best line,
+// followed by the whole principal variation as four numbers per move (the
+// first being the best move again); [] with no legal moves
+fn flatten_best_line(line: Option<chess::engine::BestLine>) -> Vec<usize> {
+    let Some(line) = line else { return vec![] };
+    let mut flat = move_coords(line.pv[0]).to_vec();
+    flat.extend([line.eval_count as usize, line.depth as usize]);
+    for move_ in line.pv {
+        flat.extend(move_coords(move_));
     }
     flat
 }
 
+// get_best_move with a time budget instead of a depth; see flatten_best_line
 #[wasm_bindgen]
-pub fn get_best_move(board: &[i8], color_int: i32, depth: i32, castling_rights: u8, use_pruning: bool, use_move_ordering: bool) -> Vec<usize> {
-    let color = if color_int == 0 {
-        chess::pieces::Color::White
-    } else {
-        chess::pieces::Color::Black
+pub fn get_best_move_timed(board: &[i8], color_int: i32, castling_rights: u8, movetime_ms: f64, use_pruning: bool, use_move_ordering: bool) -> Result<Vec<usize>, JsValue> {
+    let color = chess::pieces::Color::from_int(color_int);
+    let board_2d = board_from_js(board)?;
+    Ok(flatten_best_line(chess::engine::get_best_move_timed(&board_2d, color, castling(castling_rights), movetime_ms, use_pruning, use_move_ordering)))
+}
+
+#[wasm_bindgen]
+extern "C" {
+    // Any JS function; called with one Float64Array per finished iteration
+    pub type ProgressCallback;
+
+    #[wasm_bindgen(method, js_name = call)]
+    fn call_with(this: &ProgressCallback, context: &JsValue, info: Vec<f64>);
+}
+
+// get_best_move_timed that reports every finished iteration to on_iteration
+// as [depth, score (centipawns, white-positive), nodes, nps, elapsed_ms,
+// seldepth, then the PV as four numbers per move], for the site's thinking display
+#[wasm_bindgen]
+pub fn get_best_move_with_progress(board: &[i8], color_int: i32, castling_rights: u8, movetime_ms: f64, on_iteration: &ProgressCallback) -> Result<Vec<usize>, JsValue> {
+    let color = chess::pieces::Color::from_int(color_int);
+    let board_2d = board_from_js(board)?;
+    let budget = chess::engine::SearchBudget {
+        movetime_ms: Some(movetime_ms),
+        ..chess::engine::SearchBudget::default()
     };
 
-    let mut board_2d = [[0i8; 8]; 8];
-    for i in 0..8 {
-        for j in 0..8 {
-            board_2d[i][j] = board[i * 8 + j];
+    let mut report = |info: &chess::engine::SearchInfo| {
+        let score = if color == chess::pieces::Color::White { info.score } else { -info.score };
+        let mut flat = vec![info.depth as f64, score as f64, info.nodes as f64, info.nps(), info.elapsed_ms, info.seldepth as f64];
+        for &move_ in info.pv {
+            flat.extend(move_coords(move_).map(|n| n as f64));
         }
-    }
+        on_iteration.call_with(&JsValue::NULL, flat);
+    };
+    Ok(flatten_best_line(chess::engine::get_best_move_observed(&board_2d, color, castling(castling_rights), budget, true, true, &mut report)))
+}
+
+// Moves to mate for a centipawn score from get_best_move_with_progress or
+// get_top_lines, for showing "M3" on the eval bar: positive when the side the
+// score favors mates, 0 when the score isn't a mate
+#[wasm_bindgen]
+pub fn mate_in(score: i32) -> i32 {
+    chess::engine::mate_in(score).unwrap_or(0)
+}
 
-    let best_move = chess::engine::get_best_move(&board_2d, color, depth, castling_rights, use_pruning, use_move_ordering);
+// get_best_move capped at max_nodes searched nodes: the same move on every
+// device. Same result layout as get_best_move_timed.
+#[wasm_bindgen]
+pub fn get_best_move_nodes(board: &[i8], color_int: i32, castling_rights: u8, max_nodes: u32, use_pruning: bool, use_move_ordering: bool) -> Result<Vec<usize>, JsValue> {
+    let color = chess::pieces::Color::from_int(color_int);
+    let board_2d = board_from_js(board)?;
+    let budget = chess::engine::SearchBudget {
+        max_nodes: Some(max_nodes as u64),
+        ..chess::engine::SearchBudget::default()
+    };
+    Ok(flatten_best_line(chess::engine::get_best_move_limited(&board_2d, color, castling(castling_rights), budget, use_pruning, use_move_ordering)))
+}
 
-    match best_move {
-        Some(((from_rank, from_file), (to_rank, to_file), eval_count)) => {
-            vec![from_rank, from_file, to_rank, to_file, eval_count as usize]
+// Fixed-depth search returning the engine's expected line; same result layout
+// as get_best_move_timed
+#[wasm_bindgen]
+pub fn get_best_line(board: &[i8], color_int: i32, depth: i32, castling_rights: u8) -> Result<Vec<usize>, JsValue> {
+    let color = chess::pieces::Color::from_int(color_int);
+    let board_2d = board_from_js(board)?;
+    Ok(flatten_best_line(chess::engine::get_best_line(&board_2d, color, depth, castling(castling_rights), true, true, None)))
+}
+
+// get_best_line that sometimes plays a slightly worse move, for varied casual
+// games: temperature in centipawns, 0 for the best move (see
+// chess::engine::get_best_move_temperature). Same result layout as get_best_move_timed.
+#[wasm_bindgen]
+pub fn get_best_move_temperature(board: &[i8], color_int: i32, depth: i32, castling_rights: u8, temperature: f64) -> Result<Vec<usize>, JsValue> {
+    let color = chess::pieces::Color::from_int(color_int);
+    let board_2d = board_from_js(board)?;
+    Ok(flatten_best_line(chess::engine::get_best_move_temperature(&board_2d, color, depth, castling(castling_rights), temperature)))
+}
+
+// MultiPV for the analysis board: the `count` best moves, best first. Each
+// line is [score (centipawns, white-positive), n, then n moves of
+// from_rank, from_file, to_rank, to_file], the first move being the candidate.
+#[wasm_bindgen]
+pub fn get_top_lines(board: &[i8], color_int: i32, depth: i32, castling_rights: u8, count: usize) -> Result<Vec<i32>, JsValue> {
+    let color = chess::pieces::Color::from_int(color_int);
+    let board_2d = board_from_js(board)?;
+
+    let mut flat = Vec::new();
+    for line in chess::engine::get_top_lines(&board_2d, color, depth, castling(castling_rights), count) {
+        let score = if color == chess::pieces::Color::White { line.score } else { -line.score };
+        flat.extend([score, line.pv.len() as i32]);
+        for move_ in line.pv {
+            flat.extend(move_coords(move_).map(|n| n as i32));
         }
-        None => vec![],
     }
+    Ok(flat)
 }
 
+// get_best_move with a legality guarantee. Returns the usual five numbers plus a
+// sixth diagnostic code: 0 = searched normally, 1 = search panicked,
+// 2 = search returned nothing, 3 = search returned an illegal move,
+// 4 = search hit its node or time cap, 5 = depth out of range
+// (for 1-5 the first legal move is returned instead). Empty when there are no
+// legal moves or the board isn't a playable position.
 #[wasm_bindgen]
-pub fn is_in_check(board: &[i8], color_int: i32) -> bool {
-    let color = if color_int == 0 {
-        chess::pieces::Color::White
-    } else {
-        chess::pieces::Color::Black
+pub fn get_best_move_safe(board: &[i8], color_int: i32, depth: i32, castling_rights: u8, use_pruning: bool, use_move_ordering: bool) -> Vec<usize> {
+    let color = chess::pieces::Color::from_int(color_int);
+    let Ok(board_2d) = board_from_js(board) else {
+        return vec![];
     };
-    let board_2d = convert_flat_to_2d(&board);
-    chess::engine::is_in_check(&board_2d, color)
+
+    let (best_move, fallback) = chess::engine::get_best_move_checked(&board_2d, color, depth, castling(castling_rights), use_pruning, use_move_ordering);
+
+    let mut flat = flatten_best_move(best_move);
+    if !flat.is_empty() {
+        flat.push(fallback as usize);
+    }
+    flat
+}
+
+#[wasm_bindgen]
+pub fn get_protocol_version() -> u32 {
+    protocol::PROTOCOL_VERSION
+}
+
+// v2 of get_all_legal_moves: takes a packed state, returns one packed u32 per move
+#[wasm_bindgen]
+pub fn get_all_legal_moves_v2(state: &[i32]) -> Result<Vec<u32>, JsValue> {
+    let position = protocol::decode_state(state).map_err(|err| JsValue::from_str(&err))?;
+    let moves = chess::engine::get_legal_moves(&position.board, position.color, position.castling_rights);
+    Ok(moves
+        .into_iter()
+        .map(|move_| protocol::encode_move(&position.board, move_))
+        .collect())
+}
+
+// v2 of get_best_move: returns [packed_move, eval_count], or [] with no legal moves
+#[wasm_bindgen]
+pub fn get_best_move_v2(state: &[i32], depth: i32, use_pruning: bool, use_move_ordering: bool) -> Result<Vec<u32>, JsValue> {
+    let position = protocol::decode_state(state).map_err(|err| JsValue::from_str(&err))?;
+    let best_move = chess::engine::get_best_move(
+        &position.board,
+        position.color,
+        depth,
+        position.castling_rights,
+        use_pruning,
+        use_move_ordering,
+        None,
+    );
+    Ok(match best_move {
+        Some((from, to, eval_count)) => vec![protocol::encode_move(&position.board, (from, to)), eval_count],
+        None => vec![],
+    })
+}
+
+// Apply a packed move to a v2 state and return the new state; errors on illegal moves
+#[wasm_bindgen]
+pub fn make_move_v2(state: &[i32], packed_move: u32) -> Result<Vec<i32>, JsValue> {
+    let mut position = protocol::decode_state(state).map_err(|err| JsValue::from_str(&err))?;
+    let move_ = protocol::decode_move(packed_move);
+    if !chess::engine::get_legal_moves(&position.board, position.color, position.castling_rights).contains(&move_) {
+        return Err(JsValue::from_str("Illegal move"));
+    }
+
+    position.play(move_);
+    Ok(protocol::encode_state(&position))
+}
+
+fn piece_letters(name: &str) -> Result<chess::notation::PieceLetters, JsValue> {
+    chess::notation::PieceLetters::from_name(name).ok_or_else(|| JsValue::from_str(&format!("Unknown piece letters '{}'", name)))
+}
+
+// Piece changes between two flat boards, for animating jumps in the move list
+// or FEN loads. Groups of [kind, piece, from, to] with squares as rank * 8 + file:
+// kind 0 moved from -> to, 1 added and 2 removed (from and to both the square).
+#[wasm_bindgen]
+pub fn diff_positions(before: &[i8], after: &[i8]) -> Result<Vec<i32>, JsValue> {
+    let index = |square: chess::pieces::Square| square.index() as i32;
+    let changes = chess::diff::diff_positions(&board_from_js(before)?, &board_from_js(after)?);
+
+    let mut flat = Vec::new();
+    for change in changes {
+        flat.extend(match change {
+            chess::diff::PieceChange::Moved { piece, from, to } => [0, piece as i32, index(from), index(to)],
+            chess::diff::PieceChange::Added { piece, square } => [1, piece as i32, index(square), index(square)],
+            chess::diff::PieceChange::Removed { piece, square } => [2, piece as i32, index(square), index(square)],
+        });
+    }
+    Ok(flat)
+}
+
+// Lenient move input ("e4", "Nf3", "e2-e4", "nf3", "o-o", ...). Returns
+// [from_rank, from_file, to_rank, to_file], or [] if no single legal move matches.
+#[wasm_bindgen]
+pub fn parse_move(board: &[i8], color_int: i32, castling_rights: u8, input: &str) -> Result<Vec<usize>, JsValue> {
+    let color = chess::pieces::Color::from_int(color_int);
+    let board_2d = board_from_js(board)?;
+
+    Ok(chess::notation::parse_move_lenient(&board_2d, color, castling(castling_rights), input)
+        .map_or(vec![], |move_| move_coords(move_).to_vec()))
+}
+
+// Game status for the UI: [termination_code, winner], where termination_code
+// is 0 while the game goes on (see chess::status::Termination for the rest)
+// and winner is 0 white, 1 black, 2 nobody. history is every earlier board of
+// the game, 64 squares each, oldest first, with history_rights holding the
+// castling rights of each; it is only used for repetitions. Sides to move
+// alternate back from the current one.
+#[wasm_bindgen]
+pub fn get_game_status(
+    board: &[i8],
+    color_int: i32,
+    castling_rights: u8,
+    halfmove_clock: u32,
+    history: &[i8],
+    history_rights: &[u8],
+) -> Result<Vec<u8>, JsValue> {
+    let color = chess::pieces::Color::from_int(color_int);
+    let board_2d = board_from_js(board)?;
+    let boards = history.chunks(64).map(board_from_js).collect::<Result<Vec<_>, _>>()?;
+    if history_rights.len() != boards.len() {
+        return Err(JsValue::from_str(&format!(
+            "{} history boards but {} castling rights",
+            boards.len(),
+            history_rights.len()
+        )));
+    }
+    let history: Vec<u64> = boards
+        .iter()
+        .zip(history_rights)
+        .enumerate()
+        .map(|(index, (board, &rights))| {
+            // The last board had the other side to move, the one before it ours
+            let side = if (boards.len() - index) % 2 == 1 { chess::engine::get_opponent(color) } else { color };
+            chess::zobrist_hash(board, side, castling(rights))
+        })
+        .collect();
+
+    Ok(match chess::status::get_termination(&board_2d, color, castling(castling_rights), halfmove_clock, &history) {
+        Some(chess::status::Termination::Checkmate) => {
+            // The side to move is the one that got mated
+            vec![chess::status::Termination::Checkmate as u8, chess::engine::get_opponent(color).to_int() as u8]
+        }
+        Some(termination) => vec![termination as u8, 2],
+        None => vec![0, 2],
+    })
+}
+
+// Both players' clocks for casual games on the site. JS times each move and
+// charges it; the time control rules (increment, delay, byo-yomi) live here.
+// delay_kind is 0 for none, 1 for US delay, 2 for Bronstein.
+#[wasm_bindgen]
+pub struct ChessClock {
+    clock: chess::clock::Clock,
+}
+
+#[wasm_bindgen]
+impl ChessClock {
+    #[wasm_bindgen(constructor)]
+    pub fn new(initial_ms: u32, increment_ms: u32, delay_kind: u8, delay_ms: u32, byo_yomi_ms: u32, periods: u32) -> ChessClock {
+        let delay = match delay_kind {
+            1 => chess::clock::Delay::Us(delay_ms as u64),
+            2 => chess::clock::Delay::Bronstein(delay_ms as u64),
+            _ => chess::clock::Delay::None,
+        };
+        let control = chess::clock::TimeControl {
+            initial_ms: initial_ms as u64,
+            increment_ms: increment_ms as u64,
+            delay,
+            byo_yomi_ms: byo_yomi_ms as u64,
+            periods,
+        };
+        ChessClock {
+            clock: chess::clock::Clock::new(control),
+        }
+    }
+
+    // false when the flag fell
+    pub fn charge(&mut self, color_int: i32, elapsed_ms: f64) -> bool {
+        self.clock.charge(chess::pieces::Color::from_int(color_int), elapsed_ms.max(0.0) as u64)
+    }
+
+    // [main time ms, byo-yomi periods left, in byo-yomi 0/1]
+    pub fn state(&self, color_int: i32) -> Vec<f64> {
+        let color = chess::pieces::Color::from_int(color_int);
+        vec![
+            self.clock.remaining(color) as f64,
+            self.clock.periods_left(color) as f64,
+            self.clock.in_byo_yomi(color) as u8 as f64,
+        ]
+    }
+
+    // "300+2 delay 3" style label
+    pub fn label(&self) -> String {
+        self.clock.control.to_string()
+    }
+}
+
+// get_best_move without a worker: call step() once per requestAnimationFrame
+// tick with a node budget until it returns true, then read best_move()
+#[wasm_bindgen]
+pub struct Search {
+    search: chess::engine::ResumableSearch,
+    best_move: Option<Option<(chess::pieces::Square, chess::pieces::Square, u32)>>,
+}
+
+#[wasm_bindgen]
+impl Search {
+    #[wasm_bindgen(constructor)]
+    pub fn new(board: &[i8], color_int: i32, depth: i32, castling_rights: u8, use_pruning: bool, use_move_ordering: bool) -> Result<Search, JsValue> {
+        let color = chess::pieces::Color::from_int(color_int);
+        let board_2d = board_from_js(board)?;
+        Ok(Search {
+            search: chess::engine::ResumableSearch::new(&board_2d, color, depth, castling(castling_rights), use_pruning, use_move_ordering),
+            best_move: None,
+        })
+    }
+
+    // true once the search is complete
+    pub fn step(&mut self, budget_nodes: u32) -> bool {
+        if self.best_move.is_none() {
+            if let chess::engine::SearchStatus::Complete(best_move) = self.search.step(budget_nodes) {
+                self.best_move = Some(best_move);
+            }
+        }
+        self.best_move.is_some()
+    }
+
+    // Stop button: finish now with the best of the root moves searched so far
+    pub fn stop(&mut self) {
+        if self.best_move.is_none() {
+            self.best_move = Some(self.search.stop());
+        }
+    }
+
+    // [root moves searched, root moves in total]
+    pub fn progress(&self) -> Vec<usize> {
+        let (searched, total) = self.search.progress();
+        vec![searched, total]
+    }
+
+    // Same five numbers as get_best_move; [] while pending or with no legal moves
+    pub fn best_move(&self) -> Vec<usize> {
+        flatten_best_move(self.best_move.flatten())
+    }
+}
+
+// Game record for the move-list pane: play moves, then read back SAN and the
+// position at any ply without replaying moves in JS
+#[wasm_bindgen]
+pub struct Game {
+    game: chess::game::Game,
+}
+
+impl Default for Game {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[wasm_bindgen]
+impl Game {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Game {
+        Game {
+            game: chess::game::Game::new(),
+        }
+    }
+
+    pub fn from_fen(fen: &str) -> Result<Game, JsValue> {
+        let game = chess::game::Game::from_fen(fen).map_err(|err| JsValue::from_str(&err))?;
+        Ok(Game { game })
+    }
+
+    // Plays the move and returns what kind of move it was, for the UI's sound
+    // and animation: 0 normal, 1 capture, 2 castle, 3 promote, 4 check,
+    // 5 checkmate, 6 illegal (the game is left unchanged)
+    pub fn play_move(&mut self, from_rank: usize, from_file: usize, to_rank: usize, to_file: usize) -> u8 {
+        let Some(move_) = coords_move(from_rank, from_file, to_rank, to_file) else {
+            return chess::engine::MoveClass::Illegal as u8;
+        };
+        let position = self.game.position();
+        let class = chess::engine::classify_move(&position.board, position.color, position.castling_rights, move_);
+        if class != chess::engine::MoveClass::Illegal {
+            let _ = self.game.play(move_);
+        }
+        class as u8
+    }
+
+    // One boundary crossing for a whole turn: play the user's move, let the
+    // engine reply and report everything the UI redraws from. Returns
+    //   [0]      user move class, as from play_move
+    //   [1..5]   engine move from_rank, from_file, to_rank, to_file (-1s if none)
+    //   [5]      engine move class (-1 if none)
+    //   [6]      engine eval count
+    //   [7..9]   game status [termination_code, winner], as from get_game_status
+    //   [9..78]  v2 state after both moves (see protocol.rs)
+    // An illegal user move comes back as just [6].
+    #[allow(clippy::too_many_arguments)]
+    pub fn play_turn(
+        &mut self,
+        from_rank: usize,
+        from_file: usize,
+        to_rank: usize,
+        to_file: usize,
+        depth: i32,
+        use_pruning: bool,
+        use_move_ordering: bool,
+    ) -> Vec<i32> {
+        let user_class = self.play_move(from_rank, from_file, to_rank, to_file);
+        if user_class == chess::engine::MoveClass::Illegal as u8 {
+            return vec![user_class as i32];
+        }
+
+        let mut payload = vec![user_class as i32, -1, -1, -1, -1, -1, 0];
+        let position = self.game.position().clone();
+        if self.game.termination().is_none() {
+            // The engine steers clear of repeating the game's earlier positions
+            let best_move = chess::engine::with_game_history(&self.game.history_hashes(), position.halfmove_clock, || {
+                chess::engine::get_best_move(
+                    &position.board,
+                    position.color,
+                    depth,
+                    position.castling_rights,
+                    use_pruning,
+                    use_move_ordering,
+                    None,
+                )
+            });
+            if let Some((from, to, eval_count)) = best_move {
+                let [from_rank, from_file, to_rank, to_file] = move_coords((from, to));
+                let class = self.play_move(from_rank, from_file, to_rank, to_file);
+                payload[1..7].copy_from_slice(&[
+                    from_rank as i32,
+                    from_file as i32,
+                    to_rank as i32,
+                    to_file as i32,
+                    class as i32,
+                    eval_count as i32,
+                ]);
+            }
+        }
+
+        let position = self.game.position();
+        payload.extend(match self.game.termination() {
+            Some(chess::status::Termination::Checkmate) => {
+                [chess::status::Termination::Checkmate as i32, chess::engine::get_opponent(position.color).to_int()]
+            }
+            Some(termination) => [termination as i32, 2],
+            None => [0, 2],
+        });
+        payload.extend(protocol::encode_state(position));
+        payload
+    }
+
+    pub fn undo(&mut self) -> bool {
+        self.game.undo()
+    }
+
+    // Analysis mode: pass the move to the other side. Errors when in check.
+    pub fn play_null_move(&mut self) -> Result<(), JsValue> {
+        self.game.play_null().map(|_| ()).map_err(|err| JsValue::from_str(&err))
+    }
+
+    // Variation tree navigation. Node 0 is the starting position; the first
+    // child of a node is its main continuation.
+    pub fn current_node(&self) -> usize {
+        self.game.current_node()
+    }
+
+    pub fn go_to_node(&mut self, node: usize) -> bool {
+        self.game.go_to(node)
+    }
+
+    pub fn back(&mut self) -> bool {
+        self.game.back()
+    }
+
+    pub fn forward(&mut self) -> bool {
+        self.game.forward()
+    }
+
+    pub fn children(&self, node: usize) -> Vec<usize> {
+        self.game.children(node).to_vec()
+    }
+
+    pub fn promote_variation(&mut self, node: usize) -> bool {
+        self.game.promote_variation(node)
+    }
+
+    pub fn delete_node(&mut self, node: usize) -> bool {
+        self.game.delete(node)
+    }
+
+    pub fn from_pgn(pgn: &str) -> Result<Game, JsValue> {
+        let mut reader = chess::pgn::PgnReader::new();
+        let mut games = reader.feed(pgn);
+        games.extend(reader.finish());
+        let first = games.first().ok_or_else(|| JsValue::from_str("No game in PGN"))?;
+        let game = chess::game::Game::from_pgn(first).map_err(|err| JsValue::from_str(&err))?;
+        Ok(Game { game })
+    }
+
+    pub fn to_pgn(&self) -> String {
+        self.game.to_pgn().to_pgn()
+    }
+
+    pub fn ply_count(&self) -> usize {
+        self.game.ply()
+    }
+
+    pub fn san_moves(&self) -> Vec<String> {
+        self.game.move_list().into_iter().map(|(san, _)| san).collect()
+    }
+
+    // PGN and move list in the visitor's piece letters: "figurine", a language
+    // code such as "de" or "tr", or six letters in PNBRQK order
+    pub fn to_pgn_localized(&self, letters: &str) -> Result<String, JsValue> {
+        let letters = piece_letters(letters)?;
+        Ok(self.game.to_pgn_localized(&letters).to_pgn())
+    }
+
+    pub fn san_moves_localized(&self, letters: &str) -> Result<Vec<String>, JsValue> {
+        let letters = piece_letters(letters)?;
+        Ok(self.game.move_list().into_iter().map(|(san, _)| letters.localize(&san)).collect())
+    }
+
+    // FEN after each move, aligned with san_moves()
+    pub fn fens(&self) -> Vec<String> {
+        self.game.move_list().into_iter().map(|(_, fen)| fen).collect()
+    }
+
+    // FEN at a ply (0 is the starting position)
+    pub fn fen_at(&self, ply: usize) -> Option<String> {
+        self.game.position_at(ply).map(|position| chess::fen::to_fen(&position))
+    }
+
+    // eval_series for this game's current line, aligned with san_moves(). The
+    // line's own history and halfmove clocks (from the FEN or PGN it was loaded
+    // from) decide repetitions and the fifty-move rule along the way.
+    pub fn eval_series(&self, depth: i32) -> Vec<f32> {
+        let positions: Vec<_> = (0..=self.game.ply()).filter_map(|ply| self.game.position_at(ply)).collect();
+        scores_to_pawns(chess::engine::eval_positions(&positions, depth))
+    }
+
+    // Flat 64-square board at a ply, [] past the end of the game
+    pub fn board_at(&self, ply: usize) -> Vec<i8> {
+        self.game
+            .position_at(ply)
+            .map(|position| position.board.iter().flatten().copied().collect())
+            .unwrap_or_default()
+    }
+}
+
+// "What's the threat?": the move the opponent would play if color_int passed.
+// Returns [] when color_int is in check (passing isn't possible) or the
+// opponent has no moves.
+#[wasm_bindgen]
+pub fn get_threat(board: &[i8], color_int: i32, depth: i32, castling_rights: u8) -> Result<Vec<usize>, JsValue> {
+    let color = chess::pieces::Color::from_int(color_int);
+    let board_2d = board_from_js(board)?;
+    if chess::engine::is_in_check(&board_2d, color) {
+        return Ok(vec![]);
+    }
+
+    let opponent = chess::engine::get_opponent(color);
+    Ok(chess::engine::get_best_move(&board_2d, opponent, depth, castling(castling_rights), true, true, None)
+        .map_or(vec![], |(from, to, _)| move_coords((from, to)).to_vec()))
 }
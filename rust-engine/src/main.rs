@@ -1,69 +1,723 @@
 pub mod chess;
-use crate::chess::engine::{get_best_move, get_opponent, make_move};
+mod config;
+mod uci_client;
+use crate::chess::clock::{Clock, Delay};
+use crate::chess::engine::{
+    evaluate_board, get_best_move, get_best_move_observed, get_opponent, is_in_check, mate_in, minimax,
+    win_probability, with_game_history, with_preset,
+    SearchBudget, SearchInfo, SearchPreset,
+};
+use crate::chess::eval::PAWN_VALUE;
+use crate::chess::fen::{parse_fen, parse_square, square_name};
+use crate::chess::game::Game;
+use crate::chess::notation::move_to_san;
+use crate::chess::pgn::{PgnDeduper, PgnFilter, PgnGame, PgnReader};
 use crate::chess::pieces::*;
+use crate::chess::status::Termination;
+use crate::config::CliConfig;
+use crate::uci_client::UciEngine;
+use std::io::{BufRead, BufReader};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
 
-fn get_piece_symbol(piece: i8) -> &'static str {
-    match piece {
-        WK => "♔",
-        WQ => "♕",
-        WR => "♖",
-        WB => "♗",
-        WN => "♘",
-        WP => "♙",
+// Unicode symbol, or a dot for an empty square
+fn get_piece_symbol(piece: i8) -> char {
+    Piece::try_from(piece).map_or('·', Piece::symbol)
+}
 
-        BK => "♚",
-        BQ => "♛",
-        BR => "♜",
-        BB => "♝",
-        BN => "♞",
-        BP => "♟",
+// 256-color backgrounds for the ANSI board
+const LIGHT_SQUARE: u8 = 180;
+const DARK_SQUARE: u8 = 137;
+const LIGHT_LAST_MOVE: u8 = 186;
+const DARK_LAST_MOVE: u8 = 143;
+const CHECKED_KING: u8 = 160;
 
-        E => "·",
-        _ => "?",
-    }
-}
+const STARTING_COUNTS: [(i8, usize); 5] = [(WQ, 1), (WR, 2), (WB, 2), (WN, 2), (WP, 8)];
+
+fn print_board(board: &[[i8; 8]; 8], last_move: Option<Move>, use_color: bool) {
+    let checked_kings: Vec<i8> = [(Color::White, WK), (Color::Black, BK)]
+        .into_iter()
+        .filter(|&(color, _)| is_in_check(board, color))
+        .map(|(_, king)| king)
+        .collect();
+    let captured = captured_lines(board);
 
-fn print_board(board: &[[i8; 8]; 8]) {
-    println!("   A B C D E F G H\n");
-    for row in 0..8 {
+    if use_color {
+        println!("    A  B  C  D  E  F  G  H\n");
+    } else {
+        println!("   A B C D E F G H\n");
+    }
+    for (row, pieces) in board.iter().enumerate() {
         print!("{}  ", 8 - row);
-        for col in 0..8 {
-            let piece = board[row][col];
-            print!("{} ", get_piece_symbol(piece));
+        for (col, &piece) in pieces.iter().enumerate() {
+            if !use_color {
+                print!("{} ", get_piece_symbol(piece));
+                continue;
+            }
+            let light = (row + col) % 2 == 0;
+            let square = Square::new(row, col);
+            let moved = last_move.is_some_and(|(from, to)| from == square || to == square);
+            let background = match () {
+                _ if checked_kings.contains(&piece) => CHECKED_KING,
+                _ if moved && light => LIGHT_LAST_MOVE,
+                _ if moved => DARK_LAST_MOVE,
+                _ if light => LIGHT_SQUARE,
+                _ => DARK_SQUARE,
+            };
+            let symbol = if piece == E { ' ' } else { get_piece_symbol(piece) };
+            print!("\x1b[48;5;{}m\x1b[30m {} \x1b[0m", background, symbol);
+        }
+        // Material balance beside the top and bottom ranks
+        match row {
+            0 if !captured.1.is_empty() => print!("   {}", captured.1),
+            7 if !captured.0.is_empty() => print!("   {}", captured.0),
+            _ => {}
         }
         println!();
     }
     println!();
 }
 
+// Pieces each side has captured so far (white's first), with the material
+// lead shown next to whoever is ahead
+fn captured_lines(board: &[[i8; 8]; 8]) -> (String, String) {
+    let count = |piece: i8| board.iter().flatten().filter(|&&p| p == piece).count();
+    let mut by_white = String::new();
+    let mut by_black = String::new();
+    for (piece, start) in STARTING_COUNTS {
+        by_black.extend(std::iter::repeat_n(get_piece_symbol(piece), start.saturating_sub(count(piece))));
+        by_white.extend(std::iter::repeat_n(get_piece_symbol(-piece), start.saturating_sub(count(-piece))));
+    }
+
+    let balance = evaluate_board(board);
+    if balance > 0 {
+        by_white.push_str(&format!(" +{}", balance));
+    } else if balance < 0 {
+        by_black.push_str(&format!(" +{}", -balance));
+    }
+    (by_white, by_black)
+}
+
+fn usage() -> ! {
+    eprintln!("Usage (defaults come from chess_cli.toml, or --config FILE, when present):");
+    eprintln!("  chess_cli [watch]          watch the engine play itself until the game ends");
+    eprintln!("      --depth N              search depth (default 4)");
+    eprintln!("      --delay MS             pause after every move");
+    eprintln!("      --max-plies N          stop early after N half-moves");
+    eprintln!("      --pgn FILE             write the finished game to FILE instead of stdout");
+    eprintln!("      --color                ANSI board with last-move and check highlighting");
+    eprintln!("      --seed N               fixed tie-breaks, same game as set_random_seed(N) on the site");
+    eprintln!("  chess_cli match ENGINE     play against an external UCI engine (path to its binary)");
+    eprintln!("      --color white|black    side our engine plays (default white)");
+    eprintln!("      --depth N              our search depth (default 4)");
+    eprintln!("      --time MS              starting clock for each side (default 60000)");
+    eprintln!("      --inc MS               increment per move (default 0)");
+    eprintln!("      --delay MS             US delay: the clock starts after MS each move");
+    eprintln!("      --bronstein MS         Bronstein delay: up to MS of each move is given back");
+    eprintln!("      --byo-yomi MS          byo-yomi period length once the main time is used up");
+    eprintln!("      --periods N            number of byo-yomi periods (default 0)");
+    eprintln!("      --pgn FILE             write the game to FILE instead of stdout");
+    eprintln!("  chess_cli bench [--depth N] search the bench suite and print nodes, ms and nodes/s");
+    eprintln!("  chess_cli bench --movegen   time get_legal_moves alone on the bench positions");
+    eprintln!("      --stats                also print node, cutoff and pruning counts");
+    eprintln!("  chess_cli analyze FEN      search until Enter is pressed, then print the best move");
+    eprintln!("      --movetime MS          stop after MS instead");
+    eprintln!("      --nodes N              stop after N nodes instead");
+    eprintln!("  chess_cli filter [FILE]    print games from a PGN file (or stdin) that match:");
+    eprintln!("      --eco CODE             ECO code prefix, e.g. B2");
+    eprintln!("      --player NAME          either player's name contains NAME");
+    eprintln!("      --result RESULT        1-0, 0-1, 1/2-1/2 or *");
+    eprintln!("      --imbalance N          material difference of at least N pawns at some point");
+    eprintln!("      --fen FEN              game passes through this position");
+    eprintln!("  chess_cli dedupe [FILE]    print games from a PGN file (or stdin) without duplicates");
+    eprintln!("      --report               list the duplicates on stderr instead of dropping them");
+    eprintln!("  chess_cli uci              speak UCI on stdin/stdout, for GUIs and match runners");
+    std::process::exit(1);
+}
+
+// A search needs at least one ply to pick a move
+fn parse_depth(value: &str) -> i32 {
+    value.parse().ok().filter(|&depth| depth >= 1).unwrap_or_else(|| usage())
+}
+
+fn run_filter(args: &[String]) {
+    let mut filter = PgnFilter::default();
+    let mut path = None;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().cloned().unwrap_or_else(|| usage());
+        match arg.as_str() {
+            "--eco" => filter.eco = Some(value()),
+            "--player" => filter.player = Some(value()),
+            "--result" => filter.result = Some(value()),
+            "--imbalance" => filter.min_imbalance = Some(value().parse().unwrap_or_else(|_| usage())),
+            "--fen" => match parse_fen(&value()) {
+                Ok(position) => filter.position = Some(position),
+                Err(err) => {
+                    eprintln!("Invalid FEN: {}", err);
+                    std::process::exit(1);
+                }
+            },
+            _ if arg.starts_with("--") || path.is_some() => usage(),
+            _ => path = Some(arg.clone()),
+        }
+    }
+
+    for_each_game(path.as_deref(), |game| {
+        if filter.matches(&game) {
+            print!("{}", game.to_pgn());
+        }
+    });
+}
+
+fn run_dedupe(args: &[String]) {
+    let mut report = false;
+    let mut path = None;
+    for arg in args {
+        match arg.as_str() {
+            "--report" => report = true,
+            _ if arg.starts_with("--") || path.is_some() => usage(),
+            _ => path = Some(arg.clone()),
+        }
+    }
+
+    let mut deduper = PgnDeduper::new();
+    let mut index = 0;
+    let mut duplicates = 0;
+    for_each_game(path.as_deref(), |game| {
+        index += 1;
+        if !deduper.is_duplicate(&game) {
+            print!("{}", game.to_pgn());
+            return;
+        }
+        duplicates += 1;
+        if report {
+            eprintln!(
+                "Game {} is a duplicate: {} - {} ({})",
+                index,
+                game.tag("White").unwrap_or("?"),
+                game.tag("Black").unwrap_or("?"),
+                game.tag("Date").unwrap_or("?")
+            );
+            print!("{}", game.to_pgn());
+        }
+    });
+    eprintln!("{} games, {} duplicates", index, duplicates);
+}
+
+const MOVEGEN_BENCH_ITERATIONS: u32 = 100_000;
+
+// Same suite and output as the wasm run_bench export, so bench/compare.sh can
+// put the two side by side
+fn run_bench(args: &[String]) {
+    let show_stats = args.iter().any(|arg| arg == "--stats");
+    let args: Vec<_> = args.iter().filter(|arg| *arg != "--stats").cloned().collect();
+    let depth = match args.as_slice() {
+        [] => 4,
+        [flag, value] if flag == "--depth" => parse_depth(value),
+        [flag] if flag == "--movegen" => {
+            let (calls, elapsed) = chess::engine::bench_legal_moves(MOVEGEN_BENCH_ITERATIONS);
+            println!(
+                "calls {} ms {:.1} ns/call {:.0}",
+                calls,
+                elapsed,
+                elapsed * 1_000_000.0 / calls as f64
+            );
+            return;
+        }
+        _ => usage(),
+    };
+    let ((nodes, elapsed), stats) = if show_stats {
+        let (result, stats) = chess::engine::with_stats(|| chess::engine::bench(depth));
+        (result, Some(stats))
+    } else {
+        (chess::engine::bench(depth), None)
+    };
+    println!(
+        "depth {} nodes {} ms {:.1} nps {:.0}",
+        depth,
+        nodes,
+        elapsed,
+        nodes as f64 * 1000.0 / elapsed.max(1.0)
+    );
+    if let Some(stats) = stats {
+        println!("search nodes {} qnodes {}", stats.nodes, stats.qnodes);
+        println!(
+            "beta cutoffs by move {:?} (first move {:.1}%)",
+            stats.beta_cutoffs,
+            stats.first_move_cutoff_rate() * 100.0
+        );
+        println!(
+            "reverse futility {} razor {} futility skips {} delta skips {}",
+            stats.reverse_futility_cutoffs, stats.razor_cutoffs, stats.futility_skips, stats.delta_skips
+        );
+    }
+}
+
+// Search one position until the limits run out or Enter is pressed, then print
+// the best move of the deepest finished iteration
+fn run_analyze(args: &[String]) {
+    let Some(fen) = args.first() else { usage() };
+    let position = parse_fen(fen).unwrap_or_else(|err| {
+        eprintln!("{}", err);
+        std::process::exit(1);
+    });
+
+    let stop = Arc::new(AtomicBool::new(false));
+    let mut budget = SearchBudget {
+        stop: Some(stop.clone()),
+        ..SearchBudget::default()
+    };
+    let mut args = args[1..].iter();
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().cloned().unwrap_or_else(|| usage());
+        match arg.as_str() {
+            "--movetime" => budget.movetime_ms = Some(value().parse().unwrap_or_else(|_| usage())),
+            "--nodes" => budget.max_nodes = Some(value().parse().unwrap_or_else(|_| usage())),
+            _ => usage(),
+        }
+    }
+
+    // A line on stdin is the stop button; the thread is left behind on exit
+    std::thread::spawn(move || {
+        let mut line = String::new();
+        // EOF (no terminal) isn't a key press
+        if std::io::stdin().read_line(&mut line).is_ok_and(|read| read > 0) {
+            stop.store(true, Ordering::Relaxed);
+        }
+    });
+    eprintln!("Searching, press Enter to stop");
+
+    // One line per finished iteration, score from the side to move's view
+    let mut print_info = |info: &SearchInfo| {
+        let score = match mate_in(info.score) {
+            Some(moves) => format!("mate {}", moves),
+            None => format!("{:+.2}", info.score as f64 / PAWN_VALUE as f64),
+        };
+        println!(
+            "depth {} seldepth {} score {} nodes {} nps {:.0} pv {}",
+            info.depth,
+            info.seldepth,
+            score,
+            info.nodes,
+            info.nps(),
+            line_to_san(fen, info.pv)
+        );
+    };
+    let Some(line) = get_best_move_observed(
+        &position.board,
+        position.color,
+        position.castling_rights,
+        budget,
+        true,
+        true,
+        &mut print_info,
+    ) else {
+        println!("no legal moves");
+        return;
+    };
+    let (from, to) = line.pv[0];
+    println!(
+        "bestmove {} ({}{}) depth {} evals {}",
+        move_to_san(&position.board, position.color, position.castling_rights, (from, to)),
+        square_name(from),
+        square_name(to),
+        line.depth,
+        line.eval_count
+    );
+}
+
+// SAN of a line of moves played from `fen`
+fn line_to_san(fen: &str, line: &[Move]) -> String {
+    let mut game = Game::from_fen(fen).expect("FEN parsed before searching");
+    let mut sans = Vec::new();
+    for &move_ in line {
+        let position = game.position();
+        sans.push(move_to_san(&position.board, position.color, position.castling_rights, move_));
+        game.play(move_).expect("PV moves are legal");
+    }
+    sans.join(" ")
+}
+
+// Stream a PGN file (or stdin) one game at a time
+fn for_each_game(path: Option<&str>, mut on_game: impl FnMut(PgnGame)) {
+    let input: Box<dyn BufRead> = match path {
+        Some(path) => match std::fs::File::open(path) {
+            Ok(file) => Box::new(BufReader::new(file)),
+            Err(err) => {
+                eprintln!("Cannot open {}: {}", path, err);
+                std::process::exit(1);
+            }
+        },
+        None => Box::new(BufReader::new(std::io::stdin())),
+    };
+
+    let mut reader = PgnReader::new();
+    for line in input.lines() {
+        let Ok(line) = line else { break };
+        reader.feed(&line).into_iter().for_each(&mut on_game);
+        reader.feed("\n").into_iter().for_each(&mut on_game);
+    }
+    reader.finish().into_iter().for_each(&mut on_game);
+}
+
 fn main() {
-    let mut board: [[i8; 8]; 8] = [
-        [BR, BN, BB, BQ, BK, BB, BN, BR],
-        [BP, BP, BP, BP, BP, BP, BP, BP],
-        [E, E, E, E, E, E, E, E],
-        [E, E, E, E, E, E, E, E],
-        [E, E, E, E, E, E, E, E],
-        [E, E, E, E, E, E, E, E],
-        [WP, WP, WP, WP, WP, WP, WP, WP],
-        [WR, WN, WB, WQ, WK, WB, WN, WR],
-    ];
-    print_board(&board);
-    let mut color = Color::White;
-    let mut castling_rights = 15; // All rights
-    for _ in 0..100 {
-        let best_move = get_best_move(&board, color, 4, castling_rights);
-        match best_move {
-            Some(best_move) => {
-                println!("{}", best_move.0 .0);
-                let (_, new_rights) = make_move(&mut board, best_move, castling_rights);
-                castling_rights = new_rights;
-                print_board(&board);
-                color = get_opponent(color);
+    let mut args: Vec<String> = std::env::args().skip(1).collect();
+
+    // --config can go anywhere; the rest of the flags belong to the subcommand
+    let config_path = match args.iter().position(|arg| arg == "--config") {
+        Some(index) if index + 1 < args.len() => Some(args.drain(index..=index + 1).nth(1).unwrap()),
+        Some(_) => usage(),
+        None => None,
+    };
+    let config = CliConfig::load(config_path.as_deref()).unwrap_or_else(|err| {
+        eprintln!("{}", err);
+        std::process::exit(1);
+    });
+
+    // Every search below uses the configured preset's pruning margins
+    with_preset(config.preset, || match args.first().map(String::as_str) {
+        Some("filter") => run_filter(&args[1..]),
+        Some("dedupe") => run_dedupe(&args[1..]),
+        Some("match") => run_match(&args[1..], &config),
+        Some("watch") => run_watch(&args[1..], &config),
+        Some("bench") => run_bench(&args[1..]),
+        Some("analyze") => run_analyze(&args[1..]),
+        Some("uci") => run_uci(&config),
+        Some(arg) if arg.starts_with("--") => run_watch(&args, &config),
+        Some(_) => usage(),
+        None => run_watch(&args, &config),
+    })
+}
+
+// Engine-vs-engine game printed move by move until it ends by the rules
+fn run_watch(args: &[String], config: &CliConfig) {
+    let mut depth = config.depth;
+    let mut delay_ms = config.delay_ms;
+    let mut max_plies = config.max_plies;
+    let mut pgn_path = None;
+    let mut use_color = config.use_color;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().cloned().unwrap_or_else(|| usage());
+        match arg.as_str() {
+            "--depth" => depth = parse_depth(&value()),
+            "--delay" => delay_ms = value().parse().unwrap_or_else(|_| usage()),
+            "--max-plies" => max_plies = Some(value().parse().unwrap_or_else(|_| usage())),
+            "--pgn" => pgn_path = Some(value()),
+            "--color" => use_color = true,
+            "--seed" => chess::engine::set_seed(value().parse().unwrap_or_else(|_| usage())),
+            _ => usage(),
+        }
+    }
+
+    let mut game = Game::new();
+    print_board(&game.position().board, None, use_color);
+
+    let ending = loop {
+        let position = game.position().clone();
+        if let Some(termination) = game.termination() {
+            // Only a mate has a winner, and it is the side that just moved
+            let winner = (termination == Termination::Checkmate).then(|| get_opponent(position.color));
+            break Some((termination, winner));
+        }
+        if max_plies.is_some_and(|max| game.ply() >= max) {
+            break None;
+        }
+
+        let Some((from, to, _)) = get_best_move(
+            &position.board,
+            position.color,
+            depth,
+            position.castling_rights,
+            config.use_pruning,
+            config.use_move_ordering,
+            None,
+        ) else {
+            break None;
+        };
+        let san = move_to_san(&position.board, position.color, position.castling_rights, (from, to));
+        game.play((from, to)).expect("engine played an illegal move");
+
+        let after = game.position();
+        let mut board = after.board;
+        let mut eval_count = 0;
+        let score = minimax(
+            &mut board,
+            after.color,
+            (depth - 1).max(0),
+            -50000,
+            50000,
+            after.castling_rights,
+            config.use_pruning,
+            config.use_move_ordering,
+            &mut eval_count,
+        );
+
+        if position.color == Color::White {
+            print!("{}. {}", position.fullmove_number, san);
+        } else {
+            print!("{}... {}", position.fullmove_number, san);
+        }
+        println!(
+            "   eval {:+.2}   white wins {:.0}%",
+            score as f64 / PAWN_VALUE as f64,
+            win_probability(score, &after.board) * 100.0
+        );
+        print_board(&after.board, Some((from, to)), use_color);
+
+        if delay_ms > 0 {
+            std::thread::sleep(std::time::Duration::from_millis(delay_ms));
+        }
+    };
+
+    let mut pgn = game.to_pgn();
+    pgn.set_tag("Event", "Self-play");
+    pgn.set_tag("White", "rust-engine");
+    pgn.set_tag("Black", "rust-engine");
+    match ending {
+        Some((termination, winner)) => {
+            println!("Game over: {}", termination.description());
+            pgn.set_termination(termination, winner);
+        }
+        None => println!("Stopped after {} plies", game.ply()),
+    }
+
+    match pgn_path {
+        Some(path) => {
+            if let Err(err) = std::fs::write(&path, pgn.to_pgn()) {
+                eprintln!("Cannot write {}: {}", path, err);
+                std::process::exit(1);
+            }
+            println!("PGN written to {}", path);
+        }
+        None => print!("\n{}", pgn.to_pgn()),
+    }
+}
+
+// "42s", or "byo-yomi 3x30s" once the main time is gone
+fn clock_text(clock: &Clock, color: Color) -> String {
+    if clock.in_byo_yomi(color) {
+        format!("byo-yomi {}x{}s", clock.periods_left(color), clock.control.byo_yomi_ms / 1000)
+    } else {
+        format!("{}s", clock.remaining(color) / 1000)
+    }
+}
+
+// Our engine against an external UCI engine, with both clocks kept here.
+// Our engine searches to a fixed depth, so its clock only records how long
+// that took. Moves our rules can't express (en passant, promotion) end the
+// game by adjudication.
+fn run_match(args: &[String], config: &CliConfig) {
+    let mut engine_path = None;
+    let mut our_color = config.engine_side;
+    let mut depth = config.depth;
+    let mut time_control = config.time_control;
+    let mut pgn_path = None;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().cloned().unwrap_or_else(|| usage());
+        match arg.as_str() {
+            "--color" => {
+                our_color = match value().as_str() {
+                    "white" => Color::White,
+                    "black" => Color::Black,
+                    _ => usage(),
+                }
+            }
+            "--depth" => depth = parse_depth(&value()),
+            "--time" => time_control.initial_ms = value().parse().unwrap_or_else(|_| usage()),
+            "--inc" => time_control.increment_ms = value().parse().unwrap_or_else(|_| usage()),
+            "--delay" => time_control.delay = Delay::Us(value().parse().unwrap_or_else(|_| usage())),
+            "--bronstein" => time_control.delay = Delay::Bronstein(value().parse().unwrap_or_else(|_| usage())),
+            "--byo-yomi" => time_control.byo_yomi_ms = value().parse().unwrap_or_else(|_| usage()),
+            "--periods" => time_control.periods = value().parse().unwrap_or_else(|_| usage()),
+            "--pgn" => pgn_path = Some(value()),
+            _ if arg.starts_with("--") || engine_path.is_some() => usage(),
+            _ => engine_path = Some(arg.clone()),
+        }
+    }
+    let Some(engine_path) = engine_path else { usage() };
+
+    let mut opponent = UciEngine::spawn(&engine_path)
+        .and_then(|mut engine| engine.new_game().map(|_| engine))
+        .unwrap_or_else(|err| {
+            eprintln!("Cannot start {}: {}", engine_path, err);
+            std::process::exit(1);
+        });
+    println!("rust-engine vs {}", opponent.name);
+
+    let mut game = Game::new();
+    let mut uci_moves: Vec<String> = Vec::new();
+    let mut clock = Clock::new(time_control);
+
+    let (termination, winner) = loop {
+        let position = game.position().clone();
+        if let Some(termination) = game.termination() {
+            let winner = (termination == Termination::Checkmate).then(|| get_opponent(position.color));
+            break (termination, winner);
+        }
+
+        let started = Instant::now();
+        let move_ = if position.color == our_color {
+            get_best_move(
+                &position.board,
+                position.color,
+                depth,
+                position.castling_rights,
+                config.use_pruning,
+                config.use_move_ordering,
+                None,
+            )
+            .map(|(from, to, _)| (from, to))
+        } else {
+            match opponent.best_move(&uci_moves, &clock, position.color) {
+                Ok(text) => parse_uci_move(&text),
+                Err(err) => {
+                    eprintln!("{} stopped responding: {}", opponent.name, err);
+                    break (Termination::Adjudication, Some(our_color));
+                }
             }
-            None => {
-                println!("Game Over! No moves left.");
-                break;
+        };
+
+        if !clock.charge(position.color, started.elapsed().as_millis() as u64) {
+            break (Termination::TimeForfeit, Some(get_opponent(position.color)));
+        }
+
+        let Some(move_) = move_.filter(|&m| game.play(m).is_ok()) else {
+            eprintln!("No playable move for {}", if position.color == our_color { "rust-engine" } else { &opponent.name });
+            break (Termination::Adjudication, None);
+        };
+        let san = move_to_san(&position.board, position.color, position.castling_rights, move_);
+        uci_moves.push(format!("{}{}", square_name(move_.0), square_name(move_.1)));
+        println!(
+            "{}{} {}   (white {}, black {})",
+            position.fullmove_number,
+            if position.color == Color::White { "." } else { "..." },
+            san,
+            clock_text(&clock, Color::White),
+            clock_text(&clock, Color::Black)
+        );
+    };
+
+    let mut pgn = game.to_pgn();
+    pgn.set_tag("Event", "Engine match");
+    let (white, black) = if our_color == Color::White {
+        ("rust-engine", opponent.name.as_str())
+    } else {
+        (opponent.name.as_str(), "rust-engine")
+    };
+    pgn.set_tag("White", white);
+    pgn.set_tag("Black", black);
+    pgn.set_tag("TimeControl", &time_control.to_string());
+    pgn.set_termination(termination, winner);
+    println!("Game over: {}", termination.description());
+
+    match pgn_path {
+        Some(path) => {
+            if let Err(err) = std::fs::write(&path, pgn.to_pgn()) {
+                eprintln!("Cannot write {}: {}", path, err);
+                std::process::exit(1);
             }
+            println!("PGN written to {}", path);
         }
+        None => print!("\n{}", pgn.to_pgn()),
+    }
+}
+
+// UCI engine mode. Searches go to a fixed depth: "go depth N", or the depth of
+// the Preset option, which also sets the pruning margins. Clock arguments to
+// "go" are ignored.
+fn run_uci(config: &CliConfig) {
+    let mut preset = config.preset;
+    let mut depth = config.depth;
+    let mut game = Game::new();
+
+    for line in std::io::stdin().lock().lines() {
+        let Ok(line) = line else { break };
+        let words: Vec<&str> = line.split_whitespace().collect();
+        match words.as_slice() {
+            ["uci"] => {
+                println!("id name rust-engine");
+                let names: Vec<String> = SearchPreset::ALL.iter().map(|preset| format!("var {}", preset.name)).collect();
+                println!("option name Preset type combo default {} {}", preset.name, names.join(" "));
+                println!("uciok");
+            }
+            ["isready"] => println!("readyok"),
+            ["ucinewgame"] => game = Game::new(),
+            ["setoption", "name", name, "value", value] if name.eq_ignore_ascii_case("preset") => {
+                match SearchPreset::from_name(value) {
+                    Some(named) => {
+                        preset = named;
+                        depth = named.depth;
+                    }
+                    None => println!("info string Unknown preset '{}'", value),
+                }
+            }
+            ["position", rest @ ..] => match uci_position(rest) {
+                Ok(position) => game = position,
+                Err(err) => println!("info string {}", err),
+            },
+            ["go", rest @ ..] => {
+                let depth = match rest {
+                    ["depth", value, ..] => value.parse().ok().filter(|&depth| depth >= 1).unwrap_or(depth),
+                    _ => depth,
+                };
+                let position = game.position();
+                let best = with_preset(preset, || {
+                    with_game_history(&game.history_hashes(), position.halfmove_clock, || {
+                        get_best_move(
+                            &position.board,
+                            position.color,
+                            depth,
+                            position.castling_rights,
+                            config.use_pruning,
+                            config.use_move_ordering,
+                            None,
+                        )
+                    })
+                });
+                match best {
+                    Some((from, to, _)) => println!("bestmove {}{}", square_name(from), square_name(to)),
+                    None => println!("bestmove 0000"),
+                }
+            }
+            ["quit"] => break,
+            _ => {}
+        }
+    }
+}
+
+// "startpos [moves ...]" or "fen <six fields> [moves ...]"
+fn uci_position(words: &[&str]) -> Result<Game, String> {
+    let (mut game, rest) = match words {
+        ["startpos", rest @ ..] => (Game::new(), rest),
+        ["fen", rest @ ..] => {
+            let end = rest.iter().position(|&word| word == "moves").unwrap_or(rest.len());
+            (Game::from_fen(&rest[..end].join(" "))?, &rest[end..])
+        }
+        _ => return Err("Expected startpos or fen".to_string()),
+    };
+    let moves = match rest {
+        [] => &[][..],
+        ["moves", moves @ ..] => moves,
+        _ => return Err("Expected moves".to_string()),
+    };
+    for text in moves {
+        let move_ = parse_uci_move(text).ok_or_else(|| format!("Unsupported move '{}'", text))?;
+        game.play(move_)?;
+    }
+    Ok(game)
+}
+
+// "e2e4" -> ((6, 4), (4, 4)); promotions ("e7e8q") aren't supported
+fn parse_uci_move(text: &str) -> Option<Move> {
+    if text.len() != 4 || !text.is_ascii() {
+        return None;
     }
+    Some((parse_square(&text[..2])?, parse_square(&text[2..])?))
 }
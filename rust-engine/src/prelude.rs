@@ -0,0 +1,29 @@
+// Everything a library user normally needs, in one import:
+//
+//   use rust_engine::prelude::*;
+//
+//   let mut game = Game::new();
+//   let position = game.position().clone();
+//   if let Some((from, to, _)) = get_best_move(&position.board, position.color, 4,
+//       position.castling_rights, true, true, None) {
+//       game.play((from, to)).unwrap();
+//   }
+//
+// These names are the supported API; the rest of chess:: may change between
+// versions. Boards are [[i8; 8]; 8] with row 0 as rank 8, indexed by Square
+// (board[square]), holding the piece codes of chess::pieces (Piece converts to
+// and from them).
+
+pub use crate::chess::engine::{
+    get_best_move, get_legal_moves, is_in_check, make_move, negamax, undo_move, win_probability, with_preset,
+    CastlingRights, SearchPreset, ALL_CASTLE_RIGHTS,
+};
+pub use crate::chess::eval::{classify_game_phase, evaluate, get_game_phase, GamePhase, PAWN_VALUE, PHASE_MAX};
+pub use crate::chess::fen::{parse_fen, parse_square, square_name, to_fen, FenPosition, START_FEN};
+pub use crate::chess::game::Game;
+pub use crate::chess::handle::{EngineError, EngineHandle, EngineState};
+pub use crate::chess::notation::{move_to_san, parse_move_lenient, parse_san, PieceLetters};
+pub use crate::chess::pgn::{PgnGame, PgnReader};
+pub use crate::chess::pieces::{Color, Move, Piece, PieceType, Square};
+pub use crate::chess::status::{get_termination, Termination};
+pub use crate::chess::zobrist_hash;
@@ -0,0 +1,159 @@
+// Version 2 of the JS <-> wasm flat protocol.
+//
+// v1 (the original exports) passes a flat i8 board plus separate color and
+// castling arguments, and moves as [from_rank, from_file, to_rank, to_file]
+// quadruplets. v2 keeps the whole game state in one Int32Array and packs each
+// move into a single u32, with room for en passant and promotion. Both
+// versions stay exported while the site migrates.
+//
+// State (69 ints):
+//   [0..64]  squares, same piece codes as v1
+//   [64]     side to move (0 white, 1 black)
+//   [65]     castling rights bitmask
+//   [66]     en passant target square index (rank * 8 + file), or -1
+//   [67]     halfmove clock
+//   [68]     fullmove number
+//
+// Move (u32):
+//   bits 0-5   from square index
+//   bits 6-11  to square index
+//   bits 12-14 promotion piece type (0 none, 2 N, 3 B, 4 R, 5 Q)
+//   bit 15     capture
+//   bit 16     castling
+//   bit 17     en passant
+//
+// The engine does not generate en passant or promotions yet: the ep square and
+// halfmove clock are accepted and validated, and moves never carry the
+// promotion or en passant bits until the rules support them.
+
+use crate::chess::engine::CastlingRights;
+use crate::chess::fen::FenPosition;
+use crate::chess::pieces::{Color, Move, Square, BK, E, WK};
+
+pub const PROTOCOL_VERSION: u32 = 2;
+pub const STATE_LEN: usize = 69;
+
+pub const MOVE_CAPTURE: u32 = 1 << 15;
+pub const MOVE_CASTLE: u32 = 1 << 16;
+
+pub fn decode_state(state: &[i32]) -> Result<FenPosition, String> {
+    if state.len() != STATE_LEN {
+        return Err(format!("v2 state needs {} ints, got {}", STATE_LEN, state.len()));
+    }
+
+    let mut board = [[E; 8]; 8];
+    for (index, &piece) in state[..64].iter().enumerate() {
+        if !(-6..=6).contains(&piece) {
+            return Err(format!("Bad piece code {} on square {}", piece, index));
+        }
+        board[index / 8][index % 8] = piece as i8;
+    }
+
+    let color = match state[64] {
+        0 => Color::White,
+        1 => Color::Black,
+        other => return Err(format!("Bad side to move {}", other)),
+    };
+
+    let castling_rights = u8::try_from(state[65])
+        .ok()
+        .and_then(CastlingRights::from_bits)
+        .ok_or(format!("Bad castling rights {}", state[65]))?;
+
+    let en_passant = match state[66] {
+        -1 => None,
+        index @ 0..=63 => Square::from_index(index as usize),
+        other => return Err(format!("Bad en passant square {}", other)),
+    };
+
+    if state[67] < 0 || state[68] < 1 {
+        return Err(format!("Bad move counters {} {}", state[67], state[68]));
+    }
+
+    Ok(FenPosition {
+        board,
+        color,
+        castling_rights,
+        en_passant,
+        halfmove_clock: state[67] as u32,
+        fullmove_number: state[68] as u32,
+    })
+}
+
+pub fn encode_state(position: &FenPosition) -> Vec<i32> {
+    let mut state: Vec<i32> = position.board.iter().flatten().map(|&piece| piece as i32).collect();
+    state.push(position.color.to_int());
+    state.push(position.castling_rights.bits() as i32);
+    state.push(position.en_passant.map_or(-1, |square| square.index() as i32));
+    state.push(position.halfmove_clock as i32);
+    state.push(position.fullmove_number as i32);
+    state
+}
+
+pub fn encode_move(board: &[[i8; 8]; 8], move_: Move) -> u32 {
+    let (from, to) = move_;
+    let mut packed = from.index() as u32 | ((to.index() as u32) << 6);
+
+    let piece = board[from];
+    if board[to] != E {
+        packed |= MOVE_CAPTURE;
+    }
+    if (piece == WK || piece == BK) && from.file().abs_diff(to.file()) == 2 {
+        packed |= MOVE_CASTLE;
+    }
+    packed
+}
+
+pub fn decode_move(packed: u32) -> Move {
+    let square = |index: u32| Square::new(index as usize / 8, index as usize % 8);
+    (square(packed & 63), square((packed >> 6) & 63))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chess::fen::{parse_fen, parse_square, START_FEN};
+
+    #[test]
+    fn state_round_trips() {
+        let fen = "rnbqkbnr/ppp1pppp/8/8/3pP3/8/PPPP1PPP/RNBQKBNR b Kq e3 0 3";
+        let state = encode_state(&parse_fen(fen).unwrap());
+        assert_eq!(state.len(), STATE_LEN);
+        assert_eq!(&state[64..], &[1, 1 | 8, parse_square("e3").unwrap().index() as i32, 0, 3]);
+        assert_eq!(encode_state(&decode_state(&state).unwrap()), state);
+    }
+
+    #[test]
+    fn bad_states_are_rejected() {
+        let good = encode_state(&parse_fen(START_FEN).unwrap());
+        assert!(decode_state(&good[..68]).is_err());
+
+        let with = |index: usize, value: i32| {
+            let mut state = good.clone();
+            state[index] = value;
+            decode_state(&state)
+        };
+        assert!(with(0, 7).is_err());
+        assert!(with(64, 2).is_err());
+        assert!(with(65, 16).is_err());
+        assert!(with(66, 64).is_err());
+        assert!(with(67, -1).is_err());
+        assert!(with(68, 0).is_err());
+        assert!(with(66, -1).is_ok());
+    }
+
+    #[test]
+    fn moves_pack_flags() {
+        let position = parse_fen("r3k3/8/8/8/8/8/8/R3K2r w Q - 0 1").unwrap();
+        let castle = (parse_square("e1").unwrap(), parse_square("c1").unwrap());
+        let capture = (parse_square("a1").unwrap(), parse_square("a8").unwrap());
+
+        let packed = encode_move(&position.board, castle);
+        assert_eq!(packed & (MOVE_CASTLE | MOVE_CAPTURE), MOVE_CASTLE);
+        assert_eq!(decode_move(packed), castle);
+
+        let packed = encode_move(&position.board, capture);
+        assert_eq!(packed & (MOVE_CASTLE | MOVE_CAPTURE), MOVE_CAPTURE);
+        assert_eq!(decode_move(packed), capture);
+    }
+}
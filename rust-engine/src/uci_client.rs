@@ -0,0 +1,121 @@
+// Drives an external UCI engine (Stockfish or anything else that speaks UCI)
+// as a child process. Native only: the wasm build never sees this module.
+
+use crate::chess::clock::Clock;
+use crate::chess::pieces::Color;
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+
+pub struct UciEngine {
+    child: Child,
+    stdin: ChildStdin,
+    stdout: BufReader<ChildStdout>,
+    pub name: String,
+}
+
+impl UciEngine {
+    // Start the engine and run the uci / isready handshake
+    pub fn spawn(path: &str) -> std::io::Result<UciEngine> {
+        let mut child = Command::new(path)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()?;
+        let stdin = child.stdin.take().unwrap();
+        let stdout = BufReader::new(child.stdout.take().unwrap());
+        let mut engine = UciEngine {
+            child,
+            stdin,
+            stdout,
+            name: path.to_string(),
+        };
+
+        engine.send("uci")?;
+        loop {
+            let line = engine.read_line()?;
+            if let Some(name) = line.strip_prefix("id name ") {
+                engine.name = name.trim().to_string();
+            } else if line.trim() == "uciok" {
+                break;
+            }
+        }
+        engine.sync()?;
+        Ok(engine)
+    }
+
+    pub fn new_game(&mut self) -> std::io::Result<()> {
+        self.send("ucinewgame")?;
+        self.sync()
+    }
+
+    // Ask for a move after `moves` (coordinate notation) from the start position,
+    // with `to_move` thinking. Returns the move exactly as the engine sent it.
+    pub fn best_move(&mut self, moves: &[String], clock: &Clock, to_move: Color) -> std::io::Result<String> {
+        if moves.is_empty() {
+            self.send("position startpos")?;
+        } else {
+            self.send(&format!("position startpos moves {}", moves.join(" ")))?;
+        }
+        self.send(&go_command(clock, to_move))?;
+
+        loop {
+            let line = self.read_line()?;
+            if let Some(rest) = line.strip_prefix("bestmove ") {
+                return Ok(rest.split_whitespace().next().unwrap_or("").to_string());
+            }
+        }
+    }
+
+    fn sync(&mut self) -> std::io::Result<()> {
+        self.send("isready")?;
+        while self.read_line()?.trim() != "readyok" {}
+        Ok(())
+    }
+
+    fn send(&mut self, command: &str) -> std::io::Result<()> {
+        writeln!(self.stdin, "{}", command)?;
+        self.stdin.flush()
+    }
+
+    fn read_line(&mut self) -> std::io::Result<String> {
+        let mut line = String::new();
+        if self.stdout.read_line(&mut line)? == 0 {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::UnexpectedEof,
+                "engine closed its output",
+            ));
+        }
+        Ok(line)
+    }
+}
+
+impl Drop for UciEngine {
+    fn drop(&mut self) {
+        let _ = self.send("quit");
+        let _ = self.child.wait();
+    }
+}
+
+// UCI has no delay or byo-yomi. Delay is passed on as extra increment, which
+// is what it amounts to for a side that moves within it, and a side on
+// byo-yomi gets one period for one move.
+fn go_command(clock: &Clock, to_move: Color) -> String {
+    let time = |color| {
+        if clock.in_byo_yomi(color) {
+            clock.control.byo_yomi_ms
+        } else {
+            clock.remaining(color)
+        }
+    };
+    let increment = clock.control.increment_ms + clock.control.delay_ms();
+    let mut command = format!(
+        "go wtime {} btime {} winc {} binc {}",
+        time(Color::White),
+        time(Color::Black),
+        increment,
+        increment
+    );
+    if clock.in_byo_yomi(to_move) {
+        command.push_str(" movestogo 1");
+    }
+    command
+}
@@ -0,0 +1,49 @@
+// Golden tests: the engine must find one of the listed moves in every position
+// of tests/data/best_moves.txt. Catches tactical regressions when search or
+// evaluation internals change.
+
+use rust_engine::chess::engine::get_best_move;
+use rust_engine::chess::fen::{parse_fen, square_name};
+
+const POSITIONS: &str = include_str!("data/best_moves.txt");
+// get_best_move breaks ties at random, so give each position a few tries
+const RUNS: usize = 5;
+
+#[test]
+fn finds_golden_best_moves() {
+    let mut failures = Vec::new();
+
+    for line in POSITIONS.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let fields: Vec<&str> = line.split('|').map(str::trim).collect();
+        let [fen, depth, expected] = fields[..] else {
+            panic!("Bad line: {}", line);
+        };
+        let position = parse_fen(fen).unwrap_or_else(|err| panic!("{}: {}", fen, err));
+        let depth: i32 = depth.parse().unwrap();
+        let expected: Vec<&str> = expected.split_whitespace().collect();
+
+        for _ in 0..RUNS {
+            let found = get_best_move(
+                &position.board,
+                position.color,
+                depth,
+                position.castling_rights,
+                true,
+                true,
+                None,
+            )
+            .map(|(from, to, _)| format!("{}{}", square_name(from), square_name(to)));
+
+            if !found.as_deref().is_some_and(|m| expected.contains(&m)) {
+                failures.push(format!("{}: expected {:?}, got {:?}", fen, expected, found));
+                break;
+            }
+        }
+    }
+
+    assert!(failures.is_empty(), "\n{}", failures.join("\n"));
+}
@@ -0,0 +1,99 @@
+// Searching a position and its color-mirrored twin (board flipped top to
+// bottom, piece colors swapped, side to move and castling rights swapped) must
+// give the same side-to-move scores (negated white-positive ones) for the
+// mirrored moves, so the same moves come out best. Catches asymmetric bugs in
+// pawn direction, castling and evaluation terms.
+
+use rust_engine::chess::engine::{
+    get_legal_moves, get_opponent, make_move, negamax, CastlingRights, CASTLE_BK, CASTLE_BQ, CASTLE_WK, CASTLE_WQ,
+};
+use rust_engine::chess::eval::evaluate;
+use rust_engine::chess::fen::{parse_fen, FenPosition};
+use rust_engine::chess::pieces::{Move, Square};
+
+const POSITIONS: [&str; 8] = [
+    "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+    "r1bqkbnr/pppp1ppp/2n5/4p3/2B1P3/5Q2/PPPP1PPP/RNB1K1NR w KQkq - 0 1",
+    "r3k2r/ppp2ppp/2nqbn2/3pp3/3PP3/2NQBN2/PPP2PPP/R3K2R b KQkq - 0 1",
+    "r1b1k2r/ppppqppp/2n2n2/2b5/4P3/2N2N2/PPPP1PPP/R1BQKB1R w KQkq - 0 1",
+    "6k1/5ppp/8/8/8/8/5PPP/R5K1 w - - 0 1",
+    "8/8/4k3/8/P7/8/8/2R1K3 w - - 0 1",
+    "4k3/B7/1p6/8/8/8/5PPP/5K1R b - - 0 1",
+    "4k3/8/8/2b5/8/8/PP6/3BK3 w - - 0 1",
+];
+const DEPTH: i32 = 3;
+
+fn mirror_square(square: Square) -> Square {
+    square.flip()
+}
+
+fn mirror(position: &FenPosition) -> FenPosition {
+    let mut mirrored = position.clone();
+    for r in 0..8 {
+        for f in 0..8 {
+            mirrored.board[7 - r][f] = -position.board[r][f];
+        }
+    }
+    mirrored.color = get_opponent(position.color);
+    let rights = position.castling_rights;
+    let swap = |from: u8, to: u8| if rights.has(from) { to } else { 0 };
+    let bits = swap(CASTLE_WK, CASTLE_BK) | swap(CASTLE_WQ, CASTLE_BQ) | swap(CASTLE_BK, CASTLE_WK) | swap(CASTLE_BQ, CASTLE_WQ);
+    mirrored.castling_rights = CastlingRights::from_bits(bits).unwrap();
+    mirrored.en_passant = position.en_passant.map(mirror_square);
+    mirrored
+}
+
+// Every root move with its score for the side to move, sorted by move
+fn root_scores(position: &FenPosition) -> Vec<(Move, i32)> {
+    let mut scores: Vec<(Move, i32)> = get_legal_moves(&position.board, position.color, position.castling_rights)
+        .into_iter()
+        .map(|move_| {
+            let mut board = position.board;
+            let (_, rights) = make_move(&mut board, move_, position.castling_rights);
+            let mut eval_count = 0;
+            let score = -negamax(
+                &mut board,
+                get_opponent(position.color),
+                DEPTH - 1,
+                -50000,
+                50000,
+                rights,
+                true,
+                true,
+                &mut eval_count,
+            );
+            (move_, score)
+        })
+        .collect();
+    scores.sort();
+    scores
+}
+
+#[test]
+fn static_eval_is_mirror_symmetric() {
+    for fen in POSITIONS {
+        let position = parse_fen(fen).unwrap();
+        let mirrored = mirror(&position);
+        assert_eq!(
+            evaluate(&position.board, position.color),
+            evaluate(&mirrored.board, mirrored.color),
+            "{}",
+            fen
+        );
+    }
+}
+
+#[test]
+fn search_is_mirror_symmetric() {
+    for fen in POSITIONS {
+        let position = parse_fen(fen).unwrap();
+        let mirrored = mirror(&position);
+
+        let mut expected: Vec<(Move, i32)> = root_scores(&position)
+            .into_iter()
+            .map(|((from, to), score)| ((mirror_square(from), mirror_square(to)), score))
+            .collect();
+        expected.sort();
+        assert_eq!(expected, root_scores(&mirrored), "{}", fen);
+    }
+}